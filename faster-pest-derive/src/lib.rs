@@ -1,9 +1,10 @@
-use faster_pest_generator::Generator;
+use faster_pest_generator::{ErrorMode, Generator, Options};
 extern crate proc_macro;
 use proc_macro::TokenStream;
+use std::ops::RangeInclusive;
 
 use syn::*;
-use proc_macro2::TokenTree;
+use proc_macro2::{Span, TokenTree};
 
 fn list_grammar_files(attrs: &[Attribute]) -> Vec<String> {
     attrs.iter().filter(|attr| attr.path.is_ident("grammar")).map(|a| {
@@ -20,6 +21,75 @@ fn list_grammar_files(attrs: &[Attribute]) -> Vec<String> {
     }).collect()
 }
 
+fn parse_options(attrs: &[Attribute]) -> Options {
+    let mut options = Options::default();
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("faster_pest")) {
+        let Ok(Meta::List(list)) = attr.parse_meta() else {
+            panic!("Expected a list in faster_pest attribute, like #[faster_pest(reorder_choices)]");
+        };
+        for nested in list.nested {
+            match nested {
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("reorder_choices") => options.reorder_choices = true,
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("profile_choices") => options.profile_choices = true,
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("sentinel") => options.sentinel = true,
+                NestedMeta::Meta(Meta::NameValue(MetaNameValue { path, lit: Lit::Str(value), .. })) if path.is_ident("reorder_choices") => {
+                    options.reorder_choices = true;
+                    options.choice_profile = Some(value.value());
+                }
+                NestedMeta::Meta(Meta::NameValue(MetaNameValue { path, lit: Lit::Str(value), .. })) if path.is_ident("entry") => {
+                    options.entries.push(value.value());
+                }
+                NestedMeta::Meta(Meta::NameValue(MetaNameValue { path, lit: Lit::Str(value), .. })) if path.is_ident("errors") => {
+                    options.errors = match value.value().as_str() {
+                        "detailed" => ErrorMode::Detailed,
+                        "offset" => ErrorMode::Offset,
+                        other => panic!("Unknown error mode {other:?} in faster_pest attribute, expected \"detailed\" or \"offset\""),
+                    };
+                }
+                NestedMeta::Meta(Meta::List(list)) if list.path.is_ident("recover") => {
+                    for nested in list.nested {
+                        let NestedMeta::Meta(Meta::NameValue(MetaNameValue { path, lit: Lit::Str(terminator), .. })) = nested else {
+                            panic!("Expected rules and the terminator they skip to in recover, like #[faster_pest(recover(line = \"\\n\"))]");
+                        };
+                        let Some(rule) = path.get_ident() else {
+                            panic!("Expected a rule name in recover");
+                        };
+                        if terminator.value().is_empty() {
+                            panic!("The terminator of recovering rule {rule} must not be empty");
+                        }
+                        options.recover.push((rule.to_string(), terminator.value()));
+                    }
+                }
+                _ => panic!("Unknown option in faster_pest attribute"),
+            }
+        }
+    }
+    options
+}
+
+/// Gives all tokens the same span, recursively.
+fn respan(tokens: proc_macro2::TokenStream, span: Span) -> proc_macro2::TokenStream {
+    tokens.into_iter().map(|mut token| {
+        if let TokenTree::Group(group) = &token {
+            let mut new_group = proc_macro2::Group::new(group.delimiter(), respan(group.stream(), span));
+            new_group.set_span(span);
+            token = TokenTree::Group(new_group);
+        }
+        token.set_span(span);
+        token
+    }).collect()
+}
+
+/// Stable Rust has no way to emit warnings from proc macros, so we use a deprecated item instead.
+/// Its tokens are spanned on the user's struct so that the lint isn't silenced as coming from a macro.
+fn warning(struct_ident: &Ident, n: usize, message: &str) -> proc_macro2::TokenStream {
+    let code = include_str!("pattern_warning.rs")
+        .replace("StructIdent", &struct_ident.to_string())
+        .replace("warning_n", &n.to_string())
+        .replace("warning_message", &format!("{message:?}"));
+    respan(code.parse().expect("Unable to parse warning"), struct_ident.span())
+}
+
 struct RustGenerator {
 
 }
@@ -29,52 +99,33 @@ impl Generator for RustGenerator {
         String::from(match ident {
             "EOI" => {
                 r#"
-                pub fn parse_expr_id<'i>(input: &'i [u8]) -> Result<&'i [u8], Error> {{
-                    if input.is_empty() {{
+                pub fn parse_expr_id<'i, E: ErrorSink>(input: &'i [u8]) -> Result<&'i [u8], E::Error> {
+                    if input.len() == SENTINEL_LEN {
                         Ok(input)
-                    }} else {{
-                        Err(Error::new(ErrorKind::Expected("EOI"), unsafe{{std::str::from_utf8_unchecked(input)}}, "EOI"))
-                    }}
-                }}
-                pub fn quick_parse_expr_id<'i>(input: &'i [u8]) -> Option<&'i [u8]> {{
-                    if input.is_empty() {{
-                        Some(input)
-                    }} else {{
-                        None
-                    }}
-                }}
+                    } else {
+                        Err(E::new(ErrorKind::Expected("EOI"), input, "EOI"))
+                    }
+                }
                 "#
             },
             "SOI" => {
                 r#" // TODO
-                pub fn parse_expr_id<'i>(input: &'i [u8]) -> Result<&'i [u8], Error> {{
+                pub fn parse_expr_id<'i, E: ErrorSink>(input: &'i [u8]) -> Result<&'i [u8], E::Error> {
                     Ok(input)
-                }}
-                pub fn quick_parse_expr_id<'i>(input: &'i [u8]) -> Option<&'i [u8]> {{
-                    Some(input)
-                }}
+                }
                 "#
             }
             "NEWLINE" => {
                 r#"
-                pub fn parse_expr_id<'i>(input: &'i [u8]) -> Result<&'i [u8], Error> {{
-                    if input.starts_with(b"\r\n") {{
-                        Ok(unsafe {{ input.get_unchecked(2..) }})
-                    }} else if input.starts_with(b"\n") || input.starts_with(b"\r") {{
-                        Ok(unsafe {{ input.get_unchecked(1..) }})
-                    }} else {{
-                        Err(Error::new(ErrorKind::Expected("newline"), unsafe{{std::str::from_utf8_unchecked(input)}}, "NEWLINE"))
-                    }}
-                }}
-                pub fn quick_parse_expr_id<'i>(input: &'i [u8]) -> Option<&'i [u8]> {{
-                    if input.starts_with(b"\r\n") {{
-                        Some(unsafe {{ input.get_unchecked(2..) }})
-                    }} else if input.starts_with(b"\n") || input.starts_with(b"\r") {{
-                        Some(unsafe {{ input.get_unchecked(1..) }})
-                    }} else {{
-                        None
-                    }}
-                }}
+                pub fn parse_expr_id<'i, E: ErrorSink>(input: &'i [u8]) -> Result<&'i [u8], E::Error> {
+                    if input.starts_with(b"\r\n") {
+                        Ok(unsafe { input.get_unchecked(2..) })
+                    } else if input.starts_with(b"\n") || input.starts_with(b"\r") {
+                        Ok(unsafe { input.get_unchecked(1..) })
+                    } else {
+                        Err(E::new(ErrorKind::Expected("newline"), input, "NEWLINE"))
+                    }
+                }
                 "#
             }
             _ => ""
@@ -82,26 +133,34 @@ impl Generator for RustGenerator {
 
     }
 
-    fn character_ident(ident: &str) -> Option<&'static str> {
-        match ident {
-            "ASCII_DIGIT" => Some("c.is_ascii_digit()"),
-            "ASCII_NONZERO_DIGIT" => Some("(c.is_ascii_digit() && c != '0')"),
-            "ASCII_ALPHA_LOWER" => Some("c.is_ascii_lowercase()"),
-            "ASCII_ALPHA_UPPER" => Some("c.is_ascii_uppercase()"),
-            "ASCII_ALPHA" => Some("c.is_ascii_alphabetic()"),
-            "ASCII_ALPHANUMERIC" => Some("c.is_ascii_alphanumeric()"),
-            "ASCII" => Some("c.is_ascii()"),
-            "ANY" => Some("true"),
-            _ => None
+    fn character_class(ranges: &[RangeInclusive<u8>], negated: bool) -> String {
+        if ranges.is_empty() {
+            return negated.to_string();
+        }
+        let condition = ranges.iter().map(|range| match range.start() == range.end() {
+            true => format!("(c == &{})", range.start()),
+            false => format!("(c >= &{} && c <= &{})", range.start(), range.end()),
+        }).collect::<Vec<_>>().join(" || ");
+        match negated {
+            true => format!("!({condition})"),
+            false => condition,
         }
     }
 
-    fn character(c: u8) -> String {
-        format!("(c == &{c})")
+    fn character_lookup(table: &str) -> String {
+        format!("{table}[*c as usize]")
+    }
+
+    fn character_or_nul(condition: &str) -> String {
+        format!("({condition} || (*c == SENTINEL && !is_input_end(input, c)))")
+    }
+
+    fn pattern_choice_counters() -> &'static str {
+        include_str!("pattern_choice_counters.rs")
     }
 
-    fn character_range(c1: u8, c2: u8) -> String {
-        format!("(c >= &{c1} && c <= &{c2})")
+    fn pattern_choice_profile() -> &'static str {
+        include_str!("pattern_choice_profile.rs")
     }
 
     fn pattern_expr_character() -> &'static str {
@@ -112,6 +171,10 @@ impl Generator for RustGenerator {
         include_str!("pattern_expr_choice.rs")
     }
 
+    fn pattern_expr_fused_str() -> &'static str {
+        include_str!("pattern_expr_fused_str.rs")
+    }
+
     fn pattern_expr_insens() -> &'static str {
         include_str!("pattern_expr_insens.rs")
     }
@@ -132,6 +195,18 @@ impl Generator for RustGenerator {
         include_str!("pattern_expr_rep.rs")
     }
 
+    fn pattern_expr_rep_range_character() -> &'static str {
+        include_str!("pattern_expr_rep_range_character.rs")
+    }
+
+    fn pattern_expr_rep_range() -> &'static str {
+        include_str!("pattern_expr_rep_range.rs")
+    }
+
+    fn pattern_expr_scan_until() -> &'static str {
+        include_str!("pattern_expr_scan_until.rs")
+    }
+
     fn pattern_expr_seq() -> &'static str {
         include_str!("pattern_expr_seq.rs")
     }
@@ -140,10 +215,18 @@ impl Generator for RustGenerator {
         include_str!("pattern_expr_str.rs")
     }
 
+    fn pattern_lookup_table() -> &'static str {
+        include_str!("pattern_lookup_table.rs")
+    }
+
     fn pattern_outer() -> &'static str {
         include_str!("pattern_outer.rs")
     }
 
+    fn pattern_resume() -> &'static str {
+        include_str!("pattern_resume.rs")
+    }
+
     fn pattern_rule_method() -> &'static str {
         include_str!("pattern_rule_method.rs")
     }
@@ -155,21 +238,34 @@ impl Generator for RustGenerator {
     fn pattern_rule() -> &'static str {
         include_str!("pattern_rule.rs")
     }
+
+    fn pattern_skip_whitespace_character() -> &'static str {
+        include_str!("pattern_skip_whitespace_character.rs")
+    }
+
+    fn pattern_skip_whitespace() -> &'static str {
+        include_str!("pattern_skip_whitespace.rs")
+    }
 }
 
-#[proc_macro_derive(Parser, attributes(grammar))]
+#[proc_macro_derive(Parser, attributes(grammar, faster_pest))]
 pub fn derive_parser(input: TokenStream) -> TokenStream {
     let ast = parse_macro_input!(input as DeriveInput);
     let struct_ident = ast.ident;
 
     let grammar_files = list_grammar_files(&ast.attrs);
+    let options = parse_options(&ast.attrs);
 
-    let code = faster_pest_generator::gen::<RustGenerator>(struct_ident.to_string(), grammar_files);
+    let generated = faster_pest_generator::gen::<RustGenerator>(struct_ident.to_string(), grammar_files, &options);
 
-    let r = std::fs::write("target/fp_code.rs", &code);
+    let r = std::fs::write("target/fp_code.rs", &generated.code);
     if let Err(e) = r {
         eprintln!("Unable to write code to target/fp_code.rs: {e}");
     }
     
-    code.parse().expect("Unable to parse code")
+    let mut code: proc_macro2::TokenStream = generated.code.parse().expect("Unable to parse code");
+    for (n, message) in generated.warnings.iter().enumerate() {
+        code.extend(warning(&struct_ident, n, message));
+    }
+    code.into()
 }
@@ -0,0 +1,4 @@
+static PROFILE_expr_id: [std::sync::atomic::AtomicUsize; choice_count] = {
+    const COUNTER: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+    [COUNTER; choice_count]
+};
@@ -0,0 +1,9 @@
+impl StructIdent {
+    /// Returns how many times each alternative of each choice matched since the program started.
+    /// Write it to a file and pass it to `#[faster_pest(reorder_choices = "...")]` to optimize the order of alternatives.
+    pub fn choice_profile() -> String {
+        let mut profile = String::new();
+        profile.push_str(&format!("{}\t{}\n", PROFILE_choice_id[choice_n].load(std::sync::atomic::Ordering::Relaxed), choice_pest));
+        profile
+    }
+}
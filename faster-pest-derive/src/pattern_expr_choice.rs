@@ -1,28 +1,14 @@
 // expr_pest
-pub fn parse_expr_id<'i, 'b>(
+pub fn parse_expr_id<'i, 'b, E: ErrorSink>(
     input: &'i [u8],
-    //SIG-IDENTS idents: &'b mut Vec<(Ident<'i>, usize)>
-) -> Result<&'i [u8], Error> {
-    //SIG-IDENTS let idents_len = idents.len();
-    
-    if let Some(input) = quick_parse_choice_item_id(input, choice_idents) { return Ok(input); } unsafe { idents.set_len(idents_len); }
+    //SIG-IDENTS idents: &'b mut impl IdentSink
+) -> Result<&'i [u8], E::Error> {
+    //SIG-IDENTS let idents_len = idents.count();
+    let mut errors = E::Alternatives::default();
 
-    // TODO: remove last set_len
+    match suspending_recovery::<E, _>(choice_recovers, || parse_choice_item_id::<E>(input, choice_idents)) { Ok(input) => { /*PROFILE if counting_choices() { PROFILE_expr_id[choice_n].fetch_add(1, std::sync::atomic::Ordering::Relaxed); } PROFILE*/ return Ok(input); } Err(e) => E::push_alternative(&mut errors, e) } //SIG-IDENTS idents.rollback(idents_len);
 
-    let mut errors = Vec::new();
-    errors.push(parse_choice_item_id(input, choice_idents).unwrap_err());
+    choice_retries if E::recovers() { if let Ok(input) = parse_choice_item_id::<E>(input, choice_idents) { return Ok(input); } } //SIG-IDENTS idents.rollback(idents_len);
 
-    //SIG-IDENTS unsafe { idents.set_len(idents_len); }
-
-    Err(Error::new(ErrorKind::All(errors), unsafe{std::str::from_utf8_unchecked(input)}, r#"expr_id expr_pest"#))
-}
-pub fn quick_parse_expr_id<'i, 'b>(
-    input: &'i [u8],
-    //SIG-IDENTS idents: &'b mut Vec<(Ident<'i>, usize)>
-) -> Option<&'i [u8]> {
-    //SIG-IDENTS let idents_len = idents.len();
-
-    if let Some(input) = quick_parse_choice_item_id(input, choice_idents) { return Some(input); } unsafe { idents.set_len(idents_len); }
-
-    None
+    Err(E::all(errors, input, r#"expr_id expr_pest"#))
 }
@@ -0,0 +1,8 @@
+// expr_pest
+pub fn parse_expr_id<'i, E: ErrorSink>(input: &'i [u8]) -> Result<&'i [u8], E::Error> {
+    if input.len() - SENTINEL_LEN >= expr_str.len() && input.starts_with(bexpr_str) {
+        Ok(unsafe { input.get_unchecked(expr_str.len()..) })
+    } else {
+        Err(fused_literals_error::<E>(input, SENTINEL_LEN, &[fused_parts], r#"expr_id expr_pest"#))
+    }
+}
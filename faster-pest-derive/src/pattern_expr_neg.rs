@@ -1,31 +1,16 @@
 // expr_pest
-pub fn parse_expr_id<'i, 'b>(
+pub fn parse_expr_id<'i, 'b, E: ErrorSink>(
     input: &'i [u8],
-    //SIG-IDENTS idents: &'b mut Vec<(Ident<'i>, usize)>
-) -> Result<&'i [u8], Error> {
-    //SIG-IDENTS let idents_len = idents.len();
-    if parse_inner_id(
+    //SIG-IDENTS idents: &'b mut impl IdentSink
+) -> Result<&'i [u8], E::Error> {
+    //SIG-IDENTS let idents_len = idents.count();
+    if parse_inner_id::<NoErrors>(
         input,
         //SIG-IDENTS idents
     ).is_err() {
-        //SIG-IDENTS unsafe { idents.set_len(idents_len); }
+        //SIG-IDENTS idents.rollback(idents_len); // TODO: remove this
         Ok(input)
     } else {
-        Err(Error::new(ErrorKind::NegPredFailed("inner_id"), unsafe{std::str::from_utf8_unchecked(input)}, r#"expr_id expr_pest"#))
-    }
-}
-pub fn quick_parse_expr_id<'i, 'b>(
-    input: &'i [u8],
-    //SIG-IDENTS idents: &'b mut Vec<(Ident<'i>, usize)>
-) -> Option<&'i [u8]> {
-    //SIG-IDENTS let idents_len = idents.len();
-    if quick_parse_inner_id(
-        input,
-        //SIG-IDENTS idents
-    ).is_none() {
-        //SIG-IDENTS unsafe { idents.set_len(idents_len); } // TODO: remove this
-        Some(input)
-    } else {
-        None
+        Err(E::new(ErrorKind::NegPredFailed(inner_pest), input, r#"expr_id expr_pest"#))
     }
 }
@@ -1,22 +1,23 @@
 // expr_pest
-pub fn parse_expr_id<'i, 'b>(
+pub fn parse_expr_id<'i, 'b, E: ErrorSink>(
     mut input: &'i [u8],
-    //SIG-IDENTS idents: &'b mut Vec<(Ident<'i>, usize)>
-) -> Result<&'i [u8], Error> {
-    //NON-EMPTY input = parse_inner_eid(input, inner_idents)?;
-    while let Ok(new_input) = parse_inner_eid(input, inner_idents) {
-        input = new_input;
-        //WSP //while let Ok(new_input) = parse_WHITESPACE(input, idents) { input = new_input }
+    //SIG-IDENTS idents: &'b mut impl IdentSink
+) -> Result<&'i [u8], E::Error> {
+    //NON-EMPTY input = parse_inner_eid::<E>(input, inner_idents)?;
+    //WSP let mut matched = rep_matched;
+    loop {
+        //SIG-IDENTS let idents_len = idents.count();
+        //WSP let before = input;
+        //WSP if matched { input = skip_whitespace(input); }
+        match parse_inner_eid::<E::Discarded>(input, inner_idents) {
+            Ok(new_input) => input = new_input,
+            Err(_) => {
+                //SIG-IDENTS idents.rollback(idents_len);
+                //WSP input = before;
+                break;
+            }
+        }
+        //WSP matched = true;
     }
     Ok(input)
 }
-pub fn quick_parse_expr_id<'i, 'b>(
-    mut input: &'i [u8],
-    //SIG-IDENTS idents: &'b mut Vec<(Ident<'i>, usize)>
-) -> Option<&'i [u8]> {    //NON-EMPTY input = quick_parse_inner_eid(input, inner_idents)?;
-    while let Some(new_input) = quick_parse_inner_eid(input, inner_idents) {
-        input = new_input;
-        //WSP //while let Some(new_input) = quick_parse_WHITESPACE(input, idents) { input = new_input }
-    }
-    Some(input)
-}
@@ -1,20 +1,13 @@
 // expr_pest
-pub fn parse_expr_id<'i, 'b>(
+pub fn parse_expr_id<'i, 'b, E: ErrorSink>(
     mut input: &'i [u8],
-    //SIG-IDENTS idents: &'b mut Vec<(Ident<'i>, usize)>
-) -> Result<&'i [u8], Error> {
-    let i = input.iter().position(|c| !(character_condition)).unwrap_or(input.len());
+    //SIG-IDENTS idents: &'b mut impl IdentSink
+) -> Result<&'i [u8], E::Error> {
+    //BOUNDS let i = input.iter().position(|c| !(character_condition)).unwrap_or(input.len());
+    //SENTINEL let mut i = 0;
+    //SENTINEL while { let c = unsafe { input.get_unchecked(i) }; character_condition } { i += 1 }
     //NON-EMPTY if i == 0 {
-    //NON-EMPTY    return Err(Error::new(ErrorKind::Expected("character_condition"), unsafe{std::str::from_utf8_unchecked(input)}, "expr_id expr_pest"));
+    //NON-EMPTY    return Err(E::new(character_error_kind, input, "expr_id expr_pest"));
     //NON-EMPTY }
     Ok(unsafe { input.get_unchecked(i..) })
 }
-pub fn quick_parse_expr_id<'i, 'b>(
-    mut input: &'i [u8],
-    //SIG-IDENTS idents: &'b mut Vec<(Ident<'i>, usize)>
-) -> Option<&'i [u8]> {    let i = input.iter().position(|c| !(character_condition)).unwrap_or(input.len());
-    //NON-EMPTY if i == 0 {
-    //NON-EMPTY    return None;
-    //NON-EMPTY }
-    Some(unsafe { input.get_unchecked(i..) })
-}
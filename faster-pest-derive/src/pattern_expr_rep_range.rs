@@ -0,0 +1,28 @@
+// expr_pest
+pub fn parse_expr_id<'i, 'b, E: ErrorSink>(
+    mut input: &'i [u8],
+    //SIG-IDENTS idents: &'b mut impl IdentSink
+) -> Result<&'i [u8], E::Error> {
+    //WSP let mut matched = false;
+    for _ in 0..rep_min {
+        //WSP if matched { input = skip_whitespace(input); }
+        input = parse_inner_eid::<E>(input, inner_idents).map_err(|e| E::with_trace(e, r#"expr_id expr_pest"#))?;
+        //WSP matched = true;
+    }
+    //UP-TO for _ in rep_min..rep_max {
+    //UNBOUNDED loop {
+        //SIG-IDENTS let idents_len = idents.count();
+        //WSP let before = input;
+        //WSP if matched { input = skip_whitespace(input); }
+        match parse_inner_eid::<E::Discarded>(input, inner_idents) {
+            Ok(new_input) => input = new_input,
+            Err(_) => {
+                //SIG-IDENTS idents.rollback(idents_len);
+                //WSP input = before;
+                break;
+            }
+        }
+        //WSP matched = true;
+    }
+    Ok(input)
+}
@@ -0,0 +1,11 @@
+// expr_pest
+pub fn parse_expr_id<'i, E: ErrorSink>(input: &'i [u8]) -> Result<&'i [u8], E::Error> {
+    let i = input.iter().take(rep_min).position(|c| !(character_condition)).unwrap_or(input.len().min(rep_min));
+    if i < rep_min {
+        return Err(E::new(character_error_kind, unsafe { input.get_unchecked(i..) }, "expr_id expr_pest"));
+    }
+    let input = unsafe { input.get_unchecked(rep_min..) };
+    //UP-TO let i = input.iter().take(rep_max - rep_min).position(|c| !(character_condition)).unwrap_or(input.len().min(rep_max - rep_min));
+    //UNBOUNDED let i = input.iter().position(|c| !(character_condition)).unwrap_or(input.len());
+    Ok(unsafe { input.get_unchecked(i..) })
+}
@@ -0,0 +1,10 @@
+// expr_pest
+pub fn parse_expr_id<'i, E: ErrorSink>(input: &'i [u8]) -> Result<&'i [u8], E::Error> {
+    let i = scan_until(unsafe { input.get_unchecked(..input.len() - SENTINEL_LEN) }, bexpr_str);
+    //NON-EMPTY if input.len() == SENTINEL_LEN {
+    //NON-EMPTY     return Err(E::new(ErrorKind::Expected("ANY"), input, r#"expr_id expr_pest"#));
+    //NON-EMPTY } else if i == 0 {
+    //NON-EMPTY     return Err(E::new(ErrorKind::NegPredFailed(expr_str), input, r#"expr_id expr_pest"#));
+    //NON-EMPTY }
+    Ok(unsafe { input.get_unchecked(i..) })
+}
@@ -0,0 +1 @@
+static LOOKUP_expr_id: [bool; 256] = [lookup_values];
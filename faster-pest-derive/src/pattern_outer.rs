@@ -1,11 +1,22 @@
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
 pub enum Rule {
     RuleVariant,
+    //RECOVERING ERROR,
+}
+
+impl FromRuleName for Rule {
+    fn from_rule_name(name: &str) -> Option<Rule> {
+        match name {
+            "RuleVariant" => Some(Rule::RuleVariant),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
 pub enum Ident<'i> {
     IdentVariant(&'i str),
+    //RECOVERING ERROR(&'i str),
 }
 
 impl<'i> IdentTrait for Ident<'i> {
@@ -14,12 +25,23 @@ impl<'i> IdentTrait for Ident<'i> {
     fn as_rule(&self) -> Rule {
         match self {
             Ident::IdentVariant(_) => Rule::RuleVariant,
+            //RECOVERING Ident::ERROR(_) => Rule::ERROR,
         }
     }
 
     fn as_str(&self) -> &str {
         match self {
             Ident::IdentVariant(s) => s,
+            //RECOVERING Ident::ERROR(s) => s,
+        }
+    }
+
+    unsafe fn from_entry(variant: u16, text: &str) -> Self {
+        let text = std::mem::transmute::<&str, &'i str>(text);
+        match variant {
+            ident_index => Ident::IdentVariant(text),
+            //RECOVERING error_index => Ident::ERROR(text),
+            _ => std::hint::unreachable_unchecked(),
         }
     }
 }
@@ -27,10 +49,17 @@ impl<'i> IdentTrait for Ident<'i> {
 #[automatically_derived]
 impl StructIdent {
     pub fn parse(rule: Rule, input: &str) -> Result<Pairs2<Ident>, Error> {
-        let mut idents = Vec::with_capacity(500); // TODO: refine 500
-        match rule {
-            Rule::RuleVariant => StructIdent_faster_pest::parse_RuleVariant(input.as_bytes(), &mut idents)?,
+        check_input_len(input, StructIdent_faster_pest::SENTINEL_LEN, format_args!("{rule:?}"))?;
+        //BOUNDS let bytes = input.as_bytes();
+        //SENTINEL let padded = pad_input(input);
+        //SENTINEL let bytes = padded.as_slice();
+        let mut idents = Vec::with_capacity(idents_capacity(input));
+        let result = match rule {
+            //DETAILED Rule::RuleVariant => QuickErrors::run(bytes, "RuleVariant", || StructIdent_faster_pest::parse_RuleVariant::<QuickErrors>(bytes, &mut idents), StructIdent_faster_pest::parse_resumed::<DetailedErrors>),
+            //OFFSET Rule::RuleVariant => QuickErrors::run_offset(bytes, "RuleVariant", || StructIdent_faster_pest::parse_RuleVariant::<QuickErrors>(bytes, &mut idents), StructIdent_faster_pest::parse_resumed::<OffsetErrors>),
+            //RECOVERING Rule::ERROR => panic!("ERROR idents come from recovering rules, they can't be parsed"),
         };
+        result.map_err(|e| e.without_padding(StructIdent_faster_pest::SENTINEL_LEN))?;
         Ok(unsafe { Pairs2::from_idents(idents, input) })
     }
 }
@@ -40,5 +69,8 @@ impl StructIdent {
 mod StructIdent_faster_pest {
     use super::*;
 
+    /// Number of sentinel bytes the parsing functions expect after the input.
+    pub const SENTINEL_LEN: usize = sentinel_len;
+
     // inner code
 }
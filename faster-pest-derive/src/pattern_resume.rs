@@ -0,0 +1,7 @@
+/// Parses again from where a rule started, to describe the furthest failure of a quick pass.
+pub fn parse_resumed<'i, E: ErrorSink>(rule: &str, input: &'i [u8]) -> Result<&'i [u8], E::Error> {
+    match rule {
+        "RuleVariant" => parse_RuleVariant::<E>(input, validation_idents),
+        _ => unreachable!("no rule is named {rule}"),
+    }
+}
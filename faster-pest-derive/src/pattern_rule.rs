@@ -1,35 +1,22 @@
-pub fn parse_RuleVariant<'i, 'b>(input: &'i [u8], idents: &'b mut Vec<(Ident<'i>, usize)>) -> Result<&'i [u8], Error> {
-    let idents_len = idents.len();
-    if idents_len == idents.capacity() {
-        idents.reserve(500);
-    }
-    unsafe { idents.set_len(idents_len + 1); }
-    let new_input = match parse_top_expr_id(input, formatted_idents) {
+pub fn parse_RuleVariant<'i, 'b, E: ErrorSink>(input: &'i [u8], idents: &'b mut impl IdentSink) -> Result<&'i [u8], E::Error> {
+    let idents_len = idents.open();
+    let error_mark = E::mark();
+    let new_input = match parse_top_expr_id::<E>(input, formatted_idents) {
         Ok(input) => input,
         Err(e) => {
-            unsafe { idents.set_len(idents_len); }
-            return Err(e);
+            idents.rollback(idents_len);
+            //SKIP-ON-FAILURE if E::recovers() {
+            //SKIP-ON-FAILURE     if let Some(skipped) = recovery_skip(input, recovery_terminator, SENTINEL_LEN) {
+            //SKIP-ON-FAILURE         let node_idx = idents.open();
+            //SKIP-ON-FAILURE         unsafe { idents.close(node_idx, IdentEntry::new(error_index, input.len() - SENTINEL_LEN, skipped, idents.count())); }
+            //SKIP-ON-FAILURE         E::recovered(e, input, "RuleVariant", error_mark, node_idx);
+            //SKIP-ON-FAILURE         return Ok(unsafe { input.get_unchecked(skipped..) });
+            //SKIP-ON-FAILURE     }
+            //SKIP-ON-FAILURE }
+            return Err(E::in_rule(e, input, "RuleVariant", error_mark));
         }
     };
-    let content = unsafe { std::str::from_utf8_unchecked(input.get_unchecked(..input.len() - new_input.len())) };
-    unsafe { *idents.get_unchecked_mut(idents_len) = (Ident::IdentVariant(content), idents.len()); }
+    unsafe { idents.close(idents_len, IdentEntry::new(ident_index, input.len() - SENTINEL_LEN, input.len() - new_input.len(), idents.count())); }
+    E::rule_matched(input, "RuleVariant", error_mark);
     Ok(new_input)
 }
-
-pub fn quick_parse_RuleVariant<'i, 'b>(input: &'i [u8], idents: &'b mut Vec<(Ident<'i>, usize)>) -> Option<&'i [u8]> {
-    let idents_len = idents.len();
-    if idents_len == idents.capacity() {
-        idents.reserve(500);
-    }
-    unsafe { idents.set_len(idents_len + 1); }
-    let new_input = match quick_parse_top_expr_id(input, formatted_idents) {
-        Some(input) => input,
-        None => {
-            unsafe { idents.set_len(idents_len); }
-            return None;
-        }
-    };
-    let content = unsafe { std::str::from_utf8_unchecked(input.get_unchecked(..input.len() - new_input.len())) };
-    unsafe { *idents.get_unchecked_mut(idents_len) = (Ident::IdentVariant(content), idents.len()); }
-    Some(new_input)
-}
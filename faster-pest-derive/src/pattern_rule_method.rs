@@ -1,11 +1,64 @@
 impl StructIdent {
-    pub fn parse_RuleVariant(input: &str) -> Result<IdentList<Ident>, Error> {
-        let mut idents = Vec::with_capacity(500);
-        if quick_parse_RuleVariant(input.as_bytes(), &mut idents).is_some() {
-            return Ok(unsafe { IdentList::from_idents(idents) });
+    pub fn parse_RuleVariant(input: &str) -> Result<IdentList<'_, Ident<'_>>, Error> {
+        Self::parse_RuleVariant_into(&mut IdentBuffer::new(), input)
+    }
+
+    /// Parses using the allocation of `buffer`. Give the result back with [`IdentBuffer::recycle`] to reuse it.
+    pub fn parse_RuleVariant_into<'i>(buffer: &mut IdentBuffer, input: &'i str) -> Result<IdentList<'i, Ident<'i>>, Error> {
+        check_input_len(input, SENTINEL_LEN, "RuleVariant")?;
+        //BOUNDS let bytes = input.as_bytes();
+        //SENTINEL let padded = pad_input(input);
+        //SENTINEL let bytes = padded.as_slice();
+        let mut idents = buffer.take(idents_capacity(input));
+        //DETAILED let result = QuickErrors::run(bytes, "RuleVariant", || parse_RuleVariant::<QuickErrors>(bytes, method_idents), parse_resumed::<DetailedErrors>);
+        //OFFSET let result = QuickErrors::run_offset(bytes, "RuleVariant", || parse_RuleVariant::<QuickErrors>(bytes, method_idents), parse_resumed::<OffsetErrors>);
+        if let Err(e) = result {
+            buffer.put_back(idents);
+            return Err(e.without_padding(SENTINEL_LEN));
         }
-        idents.clear();
-        parse_RuleVariant(input.as_bytes(), &mut idents)?;
-        Ok(unsafe { IdentList::from_idents(idents) })
+        Ok(unsafe { IdentList::from_idents(idents, input) })
+    }
+
+    //SENTINEL /// Parses an input that already ends with a NUL byte, which saves copying it.
+    //SENTINEL pub fn parse_RuleVariant_padded(padded: &str) -> Result<IdentList<'_, Ident<'_>>, Error> {
+    //SENTINEL     let input = strip_sentinel(padded);
+    //SENTINEL     check_input_len(input, SENTINEL_LEN, "RuleVariant")?;
+    //SENTINEL     let mut idents = Vec::with_capacity(idents_capacity(input));
+    //SENTINEL     //DETAILED let result = QuickErrors::run(padded.as_bytes(), "RuleVariant", || parse_RuleVariant::<QuickErrors>(padded.as_bytes(), method_idents), parse_resumed::<DetailedErrors>);
+    //SENTINEL     //OFFSET let result = QuickErrors::run_offset(padded.as_bytes(), "RuleVariant", || parse_RuleVariant::<QuickErrors>(padded.as_bytes(), method_idents), parse_resumed::<OffsetErrors>);
+    //SENTINEL     result.map_err(|e| e.without_padding(SENTINEL_LEN))?;
+    //SENTINEL     Ok(unsafe { IdentList::from_idents(idents, input) })
+    //SENTINEL }
+
+    //RECOVERING /// Parses like `parse_RuleVariant`, except that rules declared with `recover` skip what they fail on instead of failing.
+    //RECOVERING /// Skipped text is returned as `ERROR` idents, along with the errors that caused them.
+    //RECOVERING pub fn parse_RuleVariant_recovering(input: &str) -> Result<(IdentList<'_, Ident<'_>>, Vec<Error>), Error> {
+    //RECOVERING     check_input_len(input, SENTINEL_LEN, "RuleVariant")?;
+    //RECOVERING     //BOUNDS let bytes = input.as_bytes();
+    //RECOVERING     //SENTINEL let padded = pad_input(input);
+    //RECOVERING     //SENTINEL let bytes = padded.as_slice();
+    //RECOVERING     let mut idents = Vec::with_capacity(idents_capacity(input));
+    //RECOVERING     //DETAILED if parse_RuleVariant::<NoErrors>(bytes, method_idents).is_ok() {
+    //RECOVERING     //DETAILED     return Ok((unsafe { IdentList::from_idents(idents, input) }, Vec::new()));
+    //RECOVERING     //DETAILED }
+    //RECOVERING     //DETAILED idents.clear();
+    //RECOVERING     //DETAILED let result = parsing_again(|| recovering(|| DetailedErrors::run(|| parse_RuleVariant::<DetailedErrors>(bytes, method_idents))));
+    //RECOVERING     //OFFSET let result = recovering(|| OffsetErrors::run(|| parse_RuleVariant::<OffsetErrors>(bytes, method_idents)));
+    //RECOVERING     let (_, recovered) = result.map_err(|e| e.without_padding(SENTINEL_LEN))?;
+    //RECOVERING     let errors = recovered_errors(&idents, error_index, recovered, SENTINEL_LEN);
+    //RECOVERING     Ok((unsafe { IdentList::from_idents(idents, input) }, errors))
+    //RECOVERING }
+
+    /// Checks that the input starts with a RuleVariant, and returns its length in bytes.
+    //BOUNDS /// No ident is stored, so this doesn't allocate unless the input is invalid.
+    //SENTINEL /// No ident is stored, so this only allocates to copy the input with the sentinel after it, and when the input is invalid.
+    pub fn validate_RuleVariant(input: &str) -> Result<usize, Error> {
+        //BOUNDS let bytes = input.as_bytes();
+        //SENTINEL let padded = pad_input(input);
+        //SENTINEL let bytes = padded.as_slice();
+        //DETAILED let rest = QuickErrors::run(bytes, "RuleVariant", || parse_RuleVariant::<QuickErrors>(bytes, validation_idents), parse_resumed::<DetailedErrors>);
+        //OFFSET let rest = QuickErrors::run_offset(bytes, "RuleVariant", || parse_RuleVariant::<QuickErrors>(bytes, validation_idents), parse_resumed::<OffsetErrors>);
+        let rest = rest.map_err(|e| e.without_padding(SENTINEL_LEN))?;
+        Ok(bytes.len() - rest.len())
     }
 }
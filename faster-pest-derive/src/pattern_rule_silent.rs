@@ -1,7 +1,6 @@
-pub fn parse_RuleVariant<'i, 'b>(input: &'i [u8], idents: &'b mut Vec<(Ident<'i>, usize)>) -> Result<&'i [u8], Error> {
-    parse_top_expr_id(input, formatted_idents)
-}
-
-pub fn quick_parse_RuleVariant<'i, 'b>(input: &'i [u8], idents: &'b mut Vec<(Ident<'i>, usize)>) -> Option<&'i [u8]> {
-    quick_parse_top_expr_id(input, formatted_idents)
+pub fn parse_RuleVariant<'i, 'b, E: ErrorSink>(
+    input: &'i [u8],
+    //SIG-IDENTS idents: &'b mut impl IdentSink
+) -> Result<&'i [u8], E::Error> {
+    parse_top_expr_id::<E>(input, formatted_idents)
 }
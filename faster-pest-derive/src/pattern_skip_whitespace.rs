@@ -0,0 +1,10 @@
+pub fn skip_whitespace<'i>(
+    mut input: &'i [u8],
+    //IDENTS idents: &mut impl IdentSink
+) -> &'i [u8] {
+    loop {
+        //WHITESPACE while let Ok(new_input) = parse_WHITESPACE::<NoErrors>(input, whitespace_idents) { input = new_input }
+        //COMMENT if let Ok(new_input) = parse_COMMENT::<NoErrors>(input, comment_idents) { input = new_input; continue }
+        return input;
+    }
+}
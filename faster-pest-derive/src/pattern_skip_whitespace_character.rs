@@ -0,0 +1,7 @@
+#[inline(always)]
+pub fn skip_whitespace<'i>(input: &'i [u8]) -> &'i [u8] {
+    //BOUNDS let i = input.iter().position(|c| !(character_condition)).unwrap_or(input.len());
+    //SENTINEL let mut i = 0;
+    //SENTINEL while { let c = unsafe { input.get_unchecked(i) }; character_condition } { i += 1 }
+    unsafe { input.get_unchecked(i..) }
+}
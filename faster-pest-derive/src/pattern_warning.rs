@@ -0,0 +1,4 @@
+#[deprecated(note = warning_message)]
+#[allow(non_upper_case_globals)]
+const FASTER_PEST_WARNING_StructIdent_warning_n: () = ();
+const _: () = FASTER_PEST_WARNING_StructIdent_warning_n;
@@ -0,0 +1,334 @@
+use crate::*;
+use std::ops::RangeInclusive;
+
+/// Builtin classes that error messages name, largest first so that they are preferred over their subsets.
+const NAMED_CLASSES: [(&str, &str); 7] = [
+    ("ASCII", "ASCII char"),
+    ("ASCII_ALPHANUMERIC", "ASCII alphanumeric"),
+    ("ASCII_ALPHA", "ASCII letter"),
+    ("ASCII_DIGIT", "ASCII digit"),
+    ("ASCII_ALPHA_LOWER", "lowercase ASCII letter"),
+    ("ASCII_ALPHA_UPPER", "uppercase ASCII letter"),
+    ("ASCII_NONZERO_DIGIT", "nonzero ASCII digit"),
+];
+
+fn quote(c: u8) -> String {
+    match c.is_ascii() {
+        true => format!("'{}'", (c as char).escape_default()),
+        false => format!("'\\u{{{c:x}}}'"),
+    }
+}
+
+/// A set of bytes that a single-character expression accepts.
+///
+/// Classes are combined by the optimizer and only rendered to code by the [`Generator`] at emission time.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CharClass {
+    bits: [u128; 2],
+}
+
+impl CharClass {
+    pub const fn empty() -> CharClass {
+        CharClass { bits: [0; 2] }
+    }
+
+    pub const fn any() -> CharClass {
+        CharClass { bits: [u128::MAX; 2] }
+    }
+
+    pub fn byte(c: u8) -> CharClass {
+        CharClass::range(c, c)
+    }
+
+    pub fn range(start: u8, end: u8) -> CharClass {
+        let mut class = CharClass::empty();
+        for c in start..=end {
+            class.bits[c as usize / 128] |= 1 << (c % 128);
+        }
+        class
+    }
+
+    /// Returns the class of a builtin pest rule, if it only ever matches a single byte.
+    pub fn builtin(ident: &str) -> Option<CharClass> {
+        match ident {
+            "ASCII_DIGIT" => Some(CharClass::range(b'0', b'9')),
+            "ASCII_NONZERO_DIGIT" => Some(CharClass::range(b'1', b'9')),
+            "ASCII_ALPHA_LOWER" => Some(CharClass::range(b'a', b'z')),
+            "ASCII_ALPHA_UPPER" => Some(CharClass::range(b'A', b'Z')),
+            "ASCII_ALPHA" => Some(CharClass::range(b'a', b'z').union(&CharClass::range(b'A', b'Z'))),
+            "ASCII_ALPHANUMERIC" => Some(CharClass::builtin("ASCII_ALPHA")?.union(&CharClass::range(b'0', b'9'))),
+            "ASCII" => Some(CharClass::range(0, 127)),
+            "ANY" => Some(CharClass::any()),
+            _ => None
+        }
+    }
+
+    pub fn contains(&self, c: u8) -> bool {
+        self.bits[c as usize / 128] & (1 << (c % 128)) != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == CharClass::empty()
+    }
+
+    pub fn is_any(&self) -> bool {
+        *self == CharClass::any()
+    }
+
+    pub fn len(&self) -> usize {
+        self.bits.iter().map(|bits| bits.count_ones() as usize).sum()
+    }
+
+    pub fn is_disjoint(&self, other: &CharClass) -> bool {
+        self.intersection(other).is_empty()
+    }
+
+    /// Returns the only byte of the class, if it contains exactly one.
+    pub fn single_byte(&self) -> Option<u8> {
+        match self.len() {
+            1 => (0..=255).find(|c| self.contains(*c)),
+            _ => None,
+        }
+    }
+
+    pub fn union(&self, other: &CharClass) -> CharClass {
+        CharClass { bits: [self.bits[0] | other.bits[0], self.bits[1] | other.bits[1]] }
+    }
+
+    pub fn intersection(&self, other: &CharClass) -> CharClass {
+        CharClass { bits: [self.bits[0] & other.bits[0], self.bits[1] & other.bits[1]] }
+    }
+
+    pub fn complement(&self) -> CharClass {
+        CharClass { bits: [!self.bits[0], !self.bits[1]] }
+    }
+
+    /// Lists the contiguous ranges of the class, in ascending order.
+    pub fn ranges(&self) -> Vec<RangeInclusive<u8>> {
+        let mut ranges = Vec::new();
+        let mut start = None;
+        for c in 0..=255u8 {
+            match (self.contains(c), start) {
+                (true, None) => start = Some(c),
+                (false, Some(s)) => {
+                    ranges.push(s..=c - 1);
+                    start = None;
+                }
+                _ => (),
+            }
+        }
+        if let Some(s) = start {
+            ranges.push(s..=255);
+        }
+        ranges
+    }
+
+    fn direct_comparisons(&self) -> usize {
+        self.ranges().iter().map(|range| if range.start() == range.end() { 1 } else { 2 }).sum()
+    }
+
+    /// Number of comparisons the rendered condition performs.
+    pub fn comparisons(&self) -> usize {
+        self.direct_comparisons().min(self.complement().direct_comparisons())
+    }
+
+    /// Evaluates the class over every byte value, when it is large enough to benefit from a lookup table.
+    pub fn lookup_table(&self) -> Option<[bool; 256]> {
+        lookup_table(self.comparisons(), |c| self.contains(c))
+    }
+
+    /// Renders the class as a condition on `c`, using the primitives of the [`Generator`].
+    pub fn to_code<G: Generator>(self) -> String {
+        let complement = self.complement();
+        match complement.direct_comparisons() < self.direct_comparisons() {
+            true => G::character_class(&complement.ranges(), true),
+            false => G::character_class(&self.ranges(), false),
+        }
+    }
+
+    /// Renders the class with pest syntax, for comments and traces.
+    pub fn to_pest(self) -> String {
+        if self.is_any() {
+            return String::from("ANY");
+        }
+        if self.is_empty() {
+            return String::from("!ANY");
+        }
+        let complement = self.complement();
+        if complement.direct_comparisons() < self.direct_comparisons() {
+            return format!("(!{} ~ ANY)", complement.to_pest());
+        }
+
+        let alternatives = self.ranges().into_iter().map(|range| match range.start() == range.end() {
+            true => quote(*range.start()),
+            false => format!("{}..{}", quote(*range.start()), quote(*range.end())),
+        }).collect::<Vec<_>>();
+        match alternatives.len() {
+            1 => alternatives.join(""),
+            _ => format!("({})", alternatives.join(" | ")),
+        }
+    }
+
+    /// Describes the class for error messages, like `ASCII digit`, `one of '.', '-'` or `any char except '"'`.
+    pub fn describe(self) -> String {
+        if self.is_any() {
+            return String::from("any char");
+        }
+        if self.is_empty() {
+            return String::from("nothing");
+        }
+        let complement = self.complement();
+        if complement.direct_comparisons() < self.direct_comparisons() {
+            return format!("any char except {}", complement.describe_items().join(", "));
+        }
+
+        let items = self.describe_items();
+        match items.len() {
+            1 => items.join(""),
+            _ => format!("one of {}", items.join(", ")),
+        }
+    }
+
+    /// Lists named builtin classes, then the remaining characters and ranges.
+    fn describe_items(self) -> Vec<String> {
+        let mut remaining = self;
+        let mut items = Vec::new();
+        for (builtin, name) in NAMED_CLASSES {
+            let class = CharClass::builtin(builtin).expect("named classes are builtins");
+            if remaining.intersection(&class) == class {
+                remaining = remaining.intersection(&class.complement());
+                items.push(name.to_string());
+            }
+        }
+        // Messages are not code, so double quotes don't need escaping
+        let quote = |c: u8| match c {
+            b'"' => String::from("'\"'"),
+            c => quote(c),
+        };
+        items.extend(remaining.ranges().into_iter().map(|range| match range.start() == range.end() {
+            true => quote(*range.start()),
+            false => format!("{}..{}", quote(*range.start()), quote(*range.end())),
+        }));
+        items
+    }
+}
+
+/// Splits a range of codepoints into sequences of byte classes that together match their UTF-8 encodings, in ascending order.
+///
+/// Ranges are split until the first and last codepoints of each part only differ in bytes that every codepoint between them may take.
+pub fn utf8_sequences(start: char, end: char) -> Vec<Vec<CharClass>> {
+    let mut sequences = Vec::new();
+    let mut ranges = vec![(start as u32, end as u32)];
+    while let Some((start, end)) = ranges.pop() {
+        if start > end {
+            continue;
+        }
+
+        // Surrogates are not chars, so the range is split around them first
+        let mut split = (start < 0xD800 && end > 0xDFFF).then_some((0xD7FF, 0xE000));
+        for max in [0x7F, 0x7FF, 0xFFFF] {
+            if split.is_none() && start <= max && max < end {
+                split = Some((max, max + 1));
+            }
+        }
+        for i in 1..4 {
+            let mask = (1 << (6 * i)) - 1;
+            if split.is_none() && start & !mask != end & !mask {
+                if start & mask != 0 {
+                    split = Some((start | mask, (start | mask) + 1));
+                } else if end & mask != mask {
+                    split = Some(((end & !mask) - 1, end & !mask));
+                }
+            }
+        }
+        if let Some((left_end, right_start)) = split {
+            ranges.push((right_start, end));
+            ranges.push((start, left_end));
+            continue;
+        }
+
+        let (mut start_buffer, mut end_buffer) = ([0; 4], [0; 4]);
+        let start = char::from_u32(start).expect("Range start").encode_utf8(&mut start_buffer).as_bytes();
+        let end = char::from_u32(end).expect("Range end").encode_utf8(&mut end_buffer).as_bytes();
+        sequences.push(start.iter().zip(end).map(|(start, end)| CharClass::range(*start, *end)).collect());
+    }
+    sequences
+}
+
+impl std::fmt::Debug for CharClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "CharClass({})", self.to_pest())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges_are_contiguous_and_ordered() {
+        let class = CharClass::range(b'a', b'c').union(&CharClass::byte(b'x')).union(&CharClass::range(250, 255));
+        assert_eq!(class.ranges(), vec![b'a'..=b'c', b'x'..=b'x', 250..=255]);
+        assert_eq!(CharClass::empty().ranges(), vec![]);
+        assert_eq!(CharClass::any().ranges(), vec![0..=255]);
+    }
+
+    #[test]
+    fn set_operations() {
+        let digits = CharClass::builtin("ASCII_DIGIT").unwrap();
+        let alphanumeric = CharClass::builtin("ASCII_ALPHANUMERIC").unwrap();
+        assert_eq!(digits.intersection(&alphanumeric), digits);
+        assert!(digits.intersection(&CharClass::builtin("ASCII_ALPHA").unwrap()).is_empty());
+        assert!(digits.union(&digits.complement()).is_any());
+    }
+
+    #[test]
+    fn complements_are_rendered_when_cheaper() {
+        let not_quote = CharClass::byte(b'"').complement();
+        assert_eq!(not_quote.comparisons(), 1);
+        assert_eq!(not_quote.to_pest(), "(!'\\\"' ~ ANY)");
+        assert!(not_quote.lookup_table().is_none());
+    }
+
+    #[test]
+    fn large_classes_use_lookup_tables() {
+        let class = CharClass::builtin("ASCII_ALPHANUMERIC").unwrap().union(&CharClass::byte(b'_'));
+        let table = class.lookup_table().unwrap();
+        assert!((0..=255u8).all(|c| table[c as usize] == class.contains(c)));
+    }
+
+    #[test]
+    fn codepoint_ranges_are_split_by_utf8_length() {
+        assert_eq!(utf8_sequences('a', 'z'), vec![vec![CharClass::range(b'a', b'z')]]);
+        assert_eq!(utf8_sequences('à', 'ÿ'), vec![vec![CharClass::byte(0xC3), CharClass::range(0xA0, 0xBF)]]);
+        assert_eq!(utf8_sequences('a', 'é'), vec![
+            vec![CharClass::range(b'a', 0x7F)],
+            vec![CharClass::byte(0xC2), CharClass::range(0x80, 0xBF)],
+            vec![CharClass::byte(0xC3), CharClass::range(0x80, 0xA9)],
+        ]);
+        assert_eq!(utf8_sequences('\u{D7FF}', '\u{E000}'), vec![
+            vec![CharClass::byte(0xED), CharClass::byte(0x9F), CharClass::byte(0xBF)],
+            vec![CharClass::byte(0xEE), CharClass::byte(0x80), CharClass::byte(0x80)],
+        ]);
+    }
+
+    #[test]
+    fn codepoint_ranges_match_exactly_their_encodings() {
+        let sequences = utf8_sequences('\u{7F0}', '\u{10400}');
+        for c in ('\u{0}'..='\u{10FFFF}').step_by(7) {
+            let mut buffer = [0; 4];
+            let bytes = c.encode_utf8(&mut buffer).as_bytes();
+            let matched = sequences.iter().any(|sequence| sequence.len() == bytes.len() && sequence.iter().zip(bytes).all(|(class, b)| class.contains(*b)));
+            assert_eq!(matched, ('\u{7F0}'..='\u{10400}').contains(&c), "{c:?}");
+        }
+    }
+
+    #[test]
+    fn descriptions_name_builtin_classes() {
+        assert_eq!(CharClass::builtin("ASCII_DIGIT").unwrap().describe(), "ASCII digit");
+        let number_char = CharClass::builtin("ASCII_DIGIT").unwrap().union(&CharClass::byte(b'.')).union(&CharClass::byte(b'+'));
+        assert_eq!(number_char.describe(), "one of ASCII digit, '+', '.'");
+        assert_eq!(CharClass::range(b'a', b'f').describe(), "'a'..'f'");
+        assert_eq!(CharClass::byte(b'"').complement().describe(), "any char except '\"'");
+    }
+}
@@ -1,9 +1,10 @@
 use crate::{*, optimizer::FPestExpr};
 
-fn to_pest(expr: &FPestExpr) -> String {
+pub fn to_pest(expr: &FPestExpr) -> String {
     match expr {
         FPestExpr::Str(s) => format!("{s:?}"),
-        FPestExpr::CharacterCondition(c) => format!("({c})"),
+        FPestExpr::FusedStr(values) => format!("({})", values.iter().map(|s| format!("{s:?}")).collect::<Vec<_>>().join(" ~ ")),
+        FPestExpr::CharacterCondition(c) => c.to_pest(),
         FPestExpr::Insens(s) => format!("^{s:?}"),
         FPestExpr::Ident(i) => i.to_owned(),
         FPestExpr::NegPred(e) => format!("!{}", to_pest(e)),
@@ -12,38 +13,94 @@ fn to_pest(expr: &FPestExpr) -> String {
         FPestExpr::Opt(e) => format!("{}?", to_pest(e)),
         FPestExpr::Rep(e, true) => format!("{}*", to_pest(e)),
         FPestExpr::Rep(e, false) => format!("{}+", to_pest(e)),
+        FPestExpr::ScanUntil(s, true) => format!("(!{s:?} ~ ANY)*"),
+        FPestExpr::ScanUntil(s, false) => format!("(!{s:?} ~ ANY)+"),
+        FPestExpr::RepRange(e, min, Some(max)) if min == max => format!("{}{{{min}}}", to_pest(e)),
+        FPestExpr::RepRange(e, 0, Some(max)) => format!("{}{{, {max}}}", to_pest(e)),
+        FPestExpr::RepRange(e, min, Some(max)) => format!("{}{{{min}, {max}}}", to_pest(e)),
+        FPestExpr::RepRange(e, min, None) => format!("{}{{{min},}}", to_pest(e)),
     }
 }
 
-pub fn code<G: Generator>(expr: &FPestExpr, ids: &mut IdRegistry, has_whitespace: bool) -> String {
+/// Rust code of the error kind of a character condition that fails.
+/// Single characters are reported like the literals they usually come from.
+fn character_error_kind(class: &CharClass) -> String {
+    match class.single_byte() {
+        Some(c) if c.is_ascii_graphic() || c == b' ' => format!("ErrorKind::ExpectedValue({:?})", (c as char).to_string()),
+        _ => format!("ErrorKind::Expected({:?})", class.describe()),
+    }
+}
+
+/// Inserts a character condition and its error kind into a template.
+/// Large classes are evaluated at compile time and replaced by a lookup in a static table.
+/// With a sentinel, the condition first tests the class without NUL, so that checks stop at the end of the input,
+/// and only then accepts the NUL bytes that the input itself contains.
+fn with_character_condition<G: Generator>(template: &str, class: &CharClass, options: &Options) -> String {
+    let template = template.replace("character_error_kind", &character_error_kind(class));
+    let without_nul = match options.sentinel {
+        true => class.intersection(&CharClass::byte(0).complement()),
+        false => *class,
+    };
+    let (condition, table) = match without_nul.lookup_table() {
+        Some(table) => {
+            let values = table.iter().map(|value| value.to_string()).collect::<Vec<_>>().join(", ");
+            (G::character_lookup("LOOKUP_expr_id"), Some(G::pattern_lookup_table().replace("lookup_values", &values)))
+        }
+        None => (without_nul.to_code::<G>(), None),
+    };
+    let condition = match without_nul != *class {
+        true => G::character_or_nul(&condition),
+        false => condition,
+    };
+    let mut code = template.replace("character_condition", &condition);
+    code.extend(table);
+    code
+}
+
+pub fn code<G: Generator>(expr: &FPestExpr, ids: &mut IdRegistry, has_whitespace: bool, whitespace_idents: bool, lexical_rules: &HashSet<String>, recovering_rules: &HashSet<String>, options: &Options) -> String {
     let id = ids.id(expr);
     let mut code = match expr {
         FPestExpr::Ident(ident) => G::ident(ident),
         FPestExpr::CharacterCondition(condition) => {
-            G::pattern_expr_character().replace("character_condition", condition)
+            with_character_condition::<G>(G::pattern_expr_character(), condition, options)
         }
         FPestExpr::Choice(items) => {
             let mut code = G::pattern_expr_choice().to_owned();
             code = multi_replace(code, vec![
                 ("choice_item_id", items.iter().map(|item| ids.id(item)).collect::<Vec<_>>()),
                 ("choice_idents", items.iter().map(|item| {
-                    match contains_idents(item, has_whitespace) {
+                    match contains_idents(item, lexical_rules, whitespace_idents) {
                         true => "idents",
                         false => "",
                     }.to_string()
                 }).collect::<Vec<_>>()),
+                ("choice_n", (0..items.len()).map(|i| i.to_string()).collect::<Vec<_>>()),
+                // Alternatives that contain recovering rules only recover once none of them matches
+                ("choice_recovers", items.iter().map(|item| can_recover(item, recovering_rules).to_string()).collect::<Vec<_>>()),
+                ("choice_retries", items.iter().map(|item| match can_recover(item, recovering_rules) {
+                    true => "",
+                    false => "//",
+                }.to_string()).collect::<Vec<_>>()),
             ]);
+            if options.profile_choices {
+                code = code.replace("/*PROFILE ", "").replace(" PROFILE*/", "");
+                code.push_str(&G::pattern_choice_counters().replace("choice_count", &items.len().to_string()));
+            }
             code
         }
         FPestExpr::Str(value) => {
             G::pattern_expr_str().replace("expr_str", format!("{value:?}").as_str())
         }
+        FPestExpr::FusedStr(values) => {
+            let literals = values.iter().map(|value| format!("{value:?}")).collect::<Vec<_>>().join(", ");
+            G::pattern_expr_fused_str().replace("fused_parts", &literals).replace("expr_str", format!("{:?}", values.concat()).as_str())
+        }
         FPestExpr::Seq(items) => {
             let mut code = G::pattern_expr_seq().to_owned();
             code = multi_replace(code, vec![
                 ("seq_item_id", items.iter().map(|item| ids.id(item)).collect::<Vec<_>>()),
                 ("seq_idents", items.iter().map(|item| {
-                    match contains_idents(item, has_whitespace) {
+                    match contains_idents(item, lexical_rules, whitespace_idents) {
                         true => "idents",
                         false => "",
                     }.to_string()
@@ -54,7 +111,7 @@ pub fn code<G: Generator>(expr: &FPestExpr, ids: &mut IdRegistry, has_whitespace
         }
         FPestExpr::Rep(expr, empty_accepted) => {
             if let FPestExpr::CharacterCondition(condition) = &**expr {
-                let mut code = G::pattern_expr_rep_character().replace("character_condition", condition);
+                let mut code = with_character_condition::<G>(G::pattern_expr_rep_character(), condition, options);
                 if !empty_accepted {
                     code = code.replace("//NON-EMPTY", "");
                 }
@@ -62,7 +119,7 @@ pub fn code<G: Generator>(expr: &FPestExpr, ids: &mut IdRegistry, has_whitespace
             } else {
                 let mut code = G::pattern_expr_rep().to_owned();
                 code = code.replace("inner_eid", &ids.id(expr));
-                code = code.replace("inner_idents", match contains_idents(expr, has_whitespace) {
+                code = code.replace("inner_idents", match contains_idents(expr, lexical_rules, whitespace_idents) {
                     true => "idents",
                     false => "",
                 });
@@ -70,18 +127,39 @@ pub fn code<G: Generator>(expr: &FPestExpr, ids: &mut IdRegistry, has_whitespace
                 if !empty_accepted {
                     code = code.replace("//NON-EMPTY", "");
                 }
-                code
+                // Whitespace is only skipped before iterations that follow another one
+                code.replace("rep_matched", &(!empty_accepted).to_string())
             }
         }
-        FPestExpr::Opt(expr) => {
-            let code = G::pattern_expr_opt().to_owned();
-            let code = code.replace("inner_eid", &ids.id(expr));
+        FPestExpr::ScanUntil(literal, empty_accepted) => {
+            let mut code = G::pattern_expr_scan_until().replace("expr_str", format!("{literal:?}").as_str());
+            if !empty_accepted {
+                code = code.replace("//NON-EMPTY", "");
+            }
             code
         }
+        FPestExpr::RepRange(expr, min, max) => {
+            let mut code = match &**expr {
+                FPestExpr::CharacterCondition(condition) => with_character_condition::<G>(G::pattern_expr_rep_range_character(), condition, options),
+                _ => {
+                    let code = G::pattern_expr_rep_range().replace("inner_eid", &ids.id(expr));
+                    code.replace("inner_idents", match contains_idents(expr, lexical_rules, whitespace_idents) {
+                        true => "idents",
+                        false => "",
+                    })
+                }
+            };
+            match max {
+                Some(max) => code = code.replace("//UP-TO", "").replace("rep_max", &max.to_string()),
+                None => code = code.replace("//UNBOUNDED", ""),
+            }
+            code.replace("rep_min", &min.to_string())
+        }
+        FPestExpr::Opt(expr) => {
+            G::pattern_expr_opt().replace("inner_eid", &ids.id(expr))
+        }
         FPestExpr::NegPred(expr) => {
-            let code = G::pattern_expr_neg().to_owned();
-            let code = code.replace("inner_id", &ids.id(expr));
-            code
+            G::pattern_expr_neg().replace("inner_id", &ids.id(expr)).replace("inner_pest", &format!("{:?}", to_pest(expr)))
         }
         FPestExpr::Insens(value) => {
             let inverted_value = value.chars().map(|c| {
@@ -95,18 +173,70 @@ pub fn code<G: Generator>(expr: &FPestExpr, ids: &mut IdRegistry, has_whitespace
             let code = G::pattern_expr_insens().to_owned();
             let code = code.replace("expr_str", format!("{value:?}").as_str());
             let code = code.replace("expr_inv_str", format!("{inverted_value:?}").as_str());
-            let code = code.replace("expr_len_str", &value.len().to_string());
-            code
+            code.replace("expr_len_str", &value.len().to_string())
         }
     };
 
     code = code.replace("expr_id", &id);
     code = code.replace("expr_pest", &to_pest(expr));
-    if contains_idents(expr, has_whitespace) {
+    if contains_idents(expr, lexical_rules, whitespace_idents) {
         code = code.replace("//SIG-IDENTS", "");
     }
     if has_whitespace {
         code = code.replace("//WSP", "");
     }
+    if whitespace_idents {
+        code = code.replace("skip_whitespace(input)", "skip_whitespace(input, idents)");
+    }
     code
 }
+
+/// Generates the `skip_whitespace` function that is called between items of sequences.
+/// When both WHITESPACE and COMMENT (if any) are lexical character classes, it becomes a single inlined scan.
+/// Otherwise, it pushes the idents of the rules that aren't lexical, so that they appear in the tree like with pest.
+pub fn skip_whitespace_code<G: Generator>(whitespace: Option<&FPestExpr>, comment: Option<&FPestExpr>, lexical_rules: &HashSet<String>, options: &Options) -> String {
+    let class = |expr: Option<&FPestExpr>, name: &str| match expr {
+        None => Some(CharClass::empty()),
+        Some(FPestExpr::CharacterCondition(class)) if lexical_rules.contains(name) => Some(*class),
+        Some(_) => None,
+    };
+    if let (Some(whitespace), Some(comment)) = (class(whitespace, "WHITESPACE"), class(comment, "COMMENT")) {
+        let code = with_character_condition::<G>(G::pattern_skip_whitespace_character(), &whitespace.union(&comment), options);
+        return code.replace("expr_id", "whitespace");
+    }
+
+    let rule_idents = |name: &str| match lexical_rules.contains(name) {
+        true => "",
+        false => "idents",
+    };
+    let mut code = G::pattern_skip_whitespace().to_owned();
+    if whitespace.is_some() {
+        code = code.replace("//WHITESPACE", "");
+    }
+    if comment.is_some() {
+        code = code.replace("//COMMENT", "");
+    }
+    if [(whitespace, "WHITESPACE"), (comment, "COMMENT")].iter().any(|(expr, name)| expr.is_some() && !lexical_rules.contains(*name)) {
+        code = code.replace("//IDENTS", "");
+    }
+    code = code.replace("whitespace_idents", rule_idents("WHITESPACE"));
+    code.replace("comment_idents", rule_idents("COMMENT"))
+}
+
+/// Generates the `choice_profile` method of instrumented parsers, listing the counters of every alternative.
+pub fn choice_profile_code<G: Generator>(exprs: &[&FPestExpr], ids: &mut IdRegistry) -> String {
+    let mut alternatives = Vec::new();
+    for expr in exprs {
+        if let FPestExpr::Choice(items) = expr {
+            for (n, item) in items.iter().enumerate() {
+                alternatives.push((ids.id(expr), n.to_string(), format!("{:?}", to_pest(item))));
+            }
+        }
+    }
+
+    multi_replace(G::pattern_choice_profile().to_owned(), vec![
+        ("choice_id", alternatives.iter().map(|(id, _, _)| id.to_owned()).collect()),
+        ("choice_n", alternatives.iter().map(|(_, n, _)| n.to_owned()).collect()),
+        ("choice_pest", alternatives.into_iter().map(|(_, _, pest)| pest).collect()),
+    ])
+}
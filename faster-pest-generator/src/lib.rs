@@ -1,6 +1,7 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::ops::RangeInclusive;
 
-use pest_meta::optimizer::OptimizedRule;
+use pest_meta::{optimizer::OptimizedRule, ast::{Expr, Rule}};
 pub(crate) use pest_meta::{optimizer::OptimizedExpr, ast::RuleType};
 
 mod ids;
@@ -11,25 +12,80 @@ mod expr_codegen;
 pub(crate) use expr_codegen::*;
 mod optimizer;
 pub(crate) use optimizer::*;
+mod lookup_table;
+pub(crate) use lookup_table::*;
+mod char_class;
+pub(crate) use char_class::*;
+
+/// Options of the generated parser, set with the `#[faster_pest(...)]` attribute.
+#[derive(Debug, Default)]
+pub struct Options {
+    /// Reorder alternatives of choices by expected frequency, when it can't change the result.
+    pub reorder_choices: bool,
+    /// Path to a profile produced by an instrumented parser, used to estimate frequencies when reordering choices.
+    /// When missing, alternatives that can start with more characters are assumed to be more frequent.
+    pub choice_profile: Option<String>,
+    /// Instrument the parser to count which alternatives of choices match.
+    pub profile_choices: bool,
+    /// Rules the parser is used from. Code is only generated for rules they can reach.
+    /// When empty, every rule is an entry.
+    pub entries: Vec<String>,
+    /// How much detail parsing errors carry.
+    pub errors: ErrorMode,
+    /// Expect a NUL byte after the input, so that character checks don't need bounds checks.
+    pub sentinel: bool,
+    /// Rules that skip to their terminator instead of failing, in the `parse_rule_recovering` methods.
+    /// The terminator isn't skipped, so it must be matched after the rule rather than by it.
+    pub recover: Vec<(String, String)>,
+}
+
+/// How much detail parsing errors carry.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorMode {
+    /// Errors have a kind, a trace of the expressions that failed, and alternatives of choices.
+    #[default]
+    Detailed,
+    /// Errors only have the furthest failure offset and its kind, so that no error code needs to allocate.
+    Offset,
+}
+
+/// Code generated by [`gen`].
+pub struct Generated {
+    pub code: String,
+    /// Problems found in the grammar, to be reported as compiler warnings.
+    pub warnings: Vec<String>,
+}
 
 pub trait Generator {
     fn ident(ident: &str) -> String;
-    fn character_ident(ident: &str) -> Option<&'static str>;
-    fn character(c: u8) -> String;
-    fn character_range(c1: u8, c2: u8) -> String;
+    /// Renders a condition on `c` that holds when it is in one of the `ranges`, or in none of them when `negated`.
+    fn character_class(ranges: &[RangeInclusive<u8>], negated: bool) -> String;
+    fn character_lookup(table: &str) -> String;
+    /// Extends a condition on a padded input so that it also accepts the NUL bytes that come before the sentinel.
+    fn character_or_nul(condition: &str) -> String;
+    fn pattern_choice_counters() -> &'static str;
+    fn pattern_choice_profile() -> &'static str;
     fn pattern_expr_character() -> &'static str;
     fn pattern_expr_choice() -> &'static str;
+    fn pattern_expr_fused_str() -> &'static str;
     fn pattern_expr_insens() -> &'static str;
     fn pattern_expr_neg() -> &'static str;
     fn pattern_expr_opt() -> &'static str;
     fn pattern_expr_rep_character() -> &'static str;
     fn pattern_expr_rep() -> &'static str;
+    fn pattern_expr_rep_range_character() -> &'static str;
+    fn pattern_expr_rep_range() -> &'static str;
+    fn pattern_expr_scan_until() -> &'static str;
     fn pattern_expr_seq() -> &'static str;
     fn pattern_expr_str() -> &'static str;
+    fn pattern_lookup_table() -> &'static str;
     fn pattern_outer() -> &'static str;
+    fn pattern_resume() -> &'static str;
     fn pattern_rule_method() -> &'static str;
     fn pattern_rule_silent() -> &'static str;
     fn pattern_rule() -> &'static str;
+    fn pattern_skip_whitespace_character() -> &'static str;
+    fn pattern_skip_whitespace() -> &'static str;
 }
 
 fn multi_replace(mut text: String, values: Vec<(&'static str, Vec<String>)>) -> String {
@@ -75,71 +131,213 @@ fn multi_replace(mut text: String, values: Vec<(&'static str, Vec<String>)>) ->
     text
 }
 
-fn get_all_rules(grammar_files: &[String]) -> Vec<OptimizedRule> {
+/// Counted repetitions like `e{2, 4}`, by the placeholder ident that replaces them in rules.
+/// They are taken out of the grammar before pest_meta unrolls them into long sequences.
+pub(crate) type CountedRepetitions = HashMap<String, (OptimizedExpr, u32, Option<u32>)>;
+
+fn get_all_rules(grammar_files: &[String]) -> (Vec<OptimizedRule>, CountedRepetitions) {
     let mut rules = HashMap::new();
+    let mut bounds = HashMap::new();
 
     for path in grammar_files {
         let Ok(grammar) = std::fs::read_to_string(path) else {
             panic!("Could not read grammar file at {path:?}");
         };
-        let (_, new_rules) = match pest_meta::parse_and_optimize(&grammar) {
+        let pairs = match pest_meta::parser::parse(pest_meta::parser::Rule::grammar_rules, &grammar) {
+            Ok(pairs) => pairs,
+            Err(e) => panic!("{e}")
+        };
+        if let Err(e) = pest_meta::validator::validate_pairs(pairs.clone()) {
+            panic!("{}", e[0])
+        }
+        let new_rules = match pest_meta::parser::consume_rules(pairs) {
             Ok(new_rules) => new_rules,
             Err(e) => panic!("{}", e[0])
         };
         for new_rule in new_rules {
-            rules.insert(new_rule.name.clone(), new_rule);
+            // Repeated expressions are optimized as rules of their own, with the type of the rule they come from
+            let mut placeholders = Vec::new();
+            let expr = new_rule.expr.map_bottom_up(|expr| {
+                let (expr, min, max) = match expr {
+                    Expr::RepExact(expr, n) => (expr, n, Some(n)),
+                    Expr::RepMin(expr, min) => (expr, min, None),
+                    Expr::RepMax(expr, max) => (expr, 0, Some(max)),
+                    Expr::RepMinMax(expr, min, max) => (expr, min, Some(max)),
+                    expr => return expr,
+                };
+                let name = format!("repetition#{}", bounds.len());
+                bounds.insert(name.clone(), (min, max));
+                placeholders.push(Rule { name: name.clone(), ty: new_rule.ty, expr: *expr });
+                Expr::Ident(name)
+            });
+            for rule in placeholders.into_iter().chain([Rule { name: new_rule.name, ty: new_rule.ty, expr }]) {
+                rules.insert(rule.name.clone(), rule);
+            }
         }
     }
 
-    let mut rules: Vec<OptimizedRule> = rules.into_values().collect();
-    rules.sort_by_key(|rule| rule.name.clone());
-    rules
+    let mut counted = CountedRepetitions::new();
+    let mut optimized_rules = Vec::new();
+    for rule in pest_meta::optimizer::optimize(rules.into_values().collect()) {
+        match bounds.get(&rule.name) {
+            Some((min, max)) => _ = counted.insert(rule.name, (rule.expr, *min, *max)),
+            None => optimized_rules.push(rule),
+        }
+    }
+    optimized_rules.sort_by_key(|rule| rule.name.clone());
+    (optimized_rules, counted)
 }
 
-pub fn gen<G: Generator>(struct_ident: String, grammar_files: Vec<String>) -> String {
-    let rules = get_all_rules(&grammar_files);
+fn read_choice_profile(path: &str) -> HashMap<String, usize> {
+    let Ok(profile) = std::fs::read_to_string(path) else {
+        panic!("Could not read choice profile at {path:?}");
+    };
+
+    let mut frequencies = HashMap::new();
+    for line in profile.lines() {
+        let Some((count, alternative)) = line.split_once('\t') else { continue };
+        let Ok(count) = count.parse::<usize>() else { continue };
+        *frequencies.entry(alternative.to_owned()).or_default() += count;
+    }
+    frequencies
+}
+
+pub fn gen<G: Generator>(struct_ident: String, grammar_files: Vec<String>, options: &Options) -> Generated {
+    let (rules, counted) = get_all_rules(&grammar_files);
 
     // Find silent rules
     let silent_rules = rules.iter().filter(|rule| matches!(rule.ty, RuleType::Silent)).map(|rule| rule.name.as_str()).collect::<Vec<_>>();
 
-    // Find if there is a rule named WHITESPACE
-    let has_whitespace = rules.iter().any(|rule| rule.name.as_str() == "WHITESPACE");
-
-    let mut full_code = G::pattern_outer().to_string();
-    full_code = multi_replace(full_code, vec![
-        ("RuleVariant", rules.iter().filter(|r| !silent_rules.contains(&r.name.as_str())).map(|rule| rule.name.as_str().to_string()).collect()),
-        ("IdentVariant", rules.iter().filter(|r| !silent_rules.contains(&r.name.as_str())).map(|rule| {
-            let name = rule.name.as_str();
-            let name_pascal_case = name.chars()
-                .next()
-                .expect("Rule name must not be empty")
-                .to_uppercase()
-                .collect::<String>()
-                + &name[1..];
-            name_pascal_case
-        }).collect()),
-    ]);
-    full_code = full_code.replace("StructIdent", struct_ident.to_string().as_str());
+    // Find if there are rules named WHITESPACE or COMMENT
+    let has_whitespace = rules.iter().any(|rule| rule.name.as_str() == "WHITESPACE" || rule.name.as_str() == "COMMENT");
 
     let mut ids = IdRegistry::new();
     let mut optimized_exprs = Vec::new();
     let mut exprs = Vec::new();
     let mut character_set_rules = HashMap::new();
     for rule in &rules {
-        let expr = optimize::<G>(&rule.expr);
+        let mut expr = optimize(&rule.expr);
+        restore_counted_repetitions(&mut expr, &counted);
         if matches!(rule.ty, RuleType::Silent) {
             if let FPestExpr::CharacterCondition(c) = &expr {
-                character_set_rules.insert(rule.name.as_str(), c.to_owned());
+                character_set_rules.insert(rule.name.as_str(), *c);
             }
         }
         optimized_exprs.push(expr);
     }
+
+    // Inline silent rules that are small or used once
+    let mut references = HashMap::new();
+    for ident in optimized_exprs.iter().flat_map(list_idents) {
+        *references.entry(ident.to_owned()).or_insert(0) += 1;
+    }
+    let inlinable = rules.iter().zip(&optimized_exprs)
+        .filter(|(rule, _)| matches!(rule.ty, RuleType::Silent) && rule.name != "WHITESPACE" && rule.name != "COMMENT")
+        .filter(|(rule, expr)| references.get(&rule.name) == Some(&1) || list_exprs(expr).len() <= INLINE_THRESHOLD)
+        .map(|(rule, expr)| (rule.name.as_str(), expr.to_owned()))
+        .collect::<HashMap<_, _>>();
+    for expr in &mut optimized_exprs {
+        inline_silent_rules(expr, &inlinable, &mut Vec::new());
+    }
+
     for expr in &mut optimized_exprs {
         optimize_second_stage(expr, &character_set_rules);
+        if !has_whitespace {
+            fuse_literals(expr);
+        }
+    }
+    if options.reorder_choices {
+        let profile = options.choice_profile.as_deref().map(read_choice_profile);
+        let frequency = |expr: &FPestExpr, first: CharClass| match &profile {
+            Some(profile) => profile.get(&to_pest(expr)).copied().unwrap_or(0),
+            None => first.len(),
+        };
+        let rule_exprs = rules.iter().map(|rule| rule.name.as_str()).zip(optimized_exprs.iter().cloned()).collect::<HashMap<_, _>>();
+        for expr in &mut optimized_exprs {
+            reorder_choices(expr, &rule_exprs, &frequency);
+        }
     }
     println!("{:#?}", optimized_exprs);
+
+    // Find silent rules that never push idents, and whether implicit whitespace does
+    let whitespace_rules = rules.iter().map(|rule| rule.name.as_str()).filter(|name| *name == "WHITESPACE" || *name == "COMMENT").collect::<Vec<_>>();
+    let lexical_rules = lexical_rules(rules.iter().zip(&optimized_exprs)
+        .filter(|(rule, _)| matches!(rule.ty, RuleType::Silent))
+        .map(|(rule, expr)| (rule.name.as_str(), expr)), &whitespace_rules);
+    let whitespace_idents = whitespace_idents(&whitespace_rules, &lexical_rules);
+
+    // Find rules that are reachable from the entry rules
+    let mut roots = whitespace_rules.clone();
+    match options.entries.is_empty() {
+        true => roots.extend(rules.iter().map(|rule| rule.name.as_str())),
+        false => for entry in &options.entries {
+            if !rules.iter().any(|rule| &rule.name == entry) {
+                panic!("Entry rule {entry:?} is not defined in the grammar");
+            }
+            roots.push(entry.as_str());
+        },
+    }
+    for (rule, _) in &options.recover {
+        match rules.iter().find(|r| &r.name == rule) {
+            None => panic!("Recovering rule {rule:?} is not defined in the grammar"),
+            Some(r) if matches!(r.ty, RuleType::Silent) => panic!("Recovering rule {rule:?} must not be silent, so that it can be replaced by an ERROR ident"),
+            Some(_) => (),
+        }
+    }
+    if !options.recover.is_empty() && rules.iter().any(|rule| rule.name == "ERROR") {
+        panic!("The ERROR rule name is reserved for the idents of skipped text when rules recover");
+    }
+    let rule_index = |name: &str| rules.iter().position(|rule| rule.name == name);
+    let references = |name: &str| match rule_index(name) {
+        Some(i) => list_idents(&optimized_exprs[i]),
+        None => Vec::new(),
+    };
+    let reachable = reachable_rules(roots.clone(), references);
+    let reachable_in_grammar = reachable_rules(roots, |name| match (rule_index(name), counted.get(name)) {
+        (Some(i), _) => list_grammar_idents(&rules[i].expr),
+        (None, Some((expr, _, _))) => list_grammar_idents(expr),
+        (None, None) => Vec::new(),
+    });
+
+    // Find rules that recover, or refer to one that does
+    let recovering_rules = match options.recover.is_empty() {
+        true => HashSet::new(),
+        false => rules.iter().map(|rule| rule.name.as_str())
+            .filter(|name| reachable_rules(vec![name], references).iter().any(|reached| options.recover.iter().any(|(rule, _)| rule == reached)))
+            .map(String::from)
+            .collect(),
+    };
+
+    // Idents are stored with the index of their variant
+    let ident_rules = rules.iter().filter(|r| !silent_rules.contains(&r.name.as_str()) && reachable.contains(r.name.as_str())).collect::<Vec<_>>();
+    let ident_index = |name: &str| ident_rules.iter().position(|rule| rule.name == name).unwrap_or_default();
+
+    let mut full_code = G::pattern_outer().to_string();
+    full_code = multi_replace(full_code, vec![
+        ("ident_index", (0..ident_rules.len()).map(|i| i.to_string()).collect()),
+        ("RuleVariant", ident_rules.iter().map(|rule| rule.name.as_str().to_string()).collect()),
+        ("IdentVariant", ident_rules.iter().map(|rule| {
+            let name = rule.name.as_str();
+            let name_pascal_case = name.chars()
+                .next()
+                .expect("Rule name must not be empty")
+                .to_uppercase()
+                .collect::<String>()
+                + &name[1..];
+            name_pascal_case
+        }).collect()),
+    ]);
+    full_code = full_code.replace("StructIdent", struct_ident.to_string().as_str());
+
     let mut inner_code = String::new();
+    let mut warnings = Vec::new();
     for (i, rule) in rules.iter().enumerate() {
+        if !reachable_in_grammar.contains(rule.name.as_str()) {
+            warnings.push(format!("rule `{}` is unreachable from the entry rules of `{struct_ident}`", rule.name));
+        }
+        if !reachable.contains(rule.name.as_str()) {
+            continue;
+        }
         let expr = optimized_exprs.get(i).expect("Expr not found");
         exprs.extend(list_exprs(expr));
         let rule_name = rule.name.as_str();
@@ -150,27 +348,59 @@ pub fn gen<G: Generator>(struct_ident: String, grammar_files: Vec<String>) -> St
             .collect::<String>()
             + &rule_name[1..];
         let top_expr_id = ids.id(expr);
-        let formatted_idents = match contains_idents(expr, has_whitespace) {
+        let formatted_idents = match contains_idents(expr, &lexical_rules, whitespace_idents) {
             true => "idents",
             false => "",
         };
+        let is_lexical = lexical_rules.contains(rule_name);
+        let (method_idents, validation_idents) = match is_lexical {
+            true => ("", ""),
+            false => ("&mut idents", "&mut NoIdents"),
+        };
 
         let mut code = match silent_rules.contains(&rule_name) {
             false => G::pattern_rule().to_string(),
             true => G::pattern_rule_silent().to_string(),
         };
-        code.push_str(G::pattern_rule_method());
+        if options.entries.is_empty() || options.entries.contains(&rule.name) {
+            code.push_str(G::pattern_rule_method());
+        }
         code = code.replace("RuleVariant", rule.name.as_str());
         code = code.replace("top_expr_id", top_expr_id.to_string().as_str());
+        code = code.replace("ident_index", &ident_index(rule_name).to_string());
         code = code.replace("formatted_idents", formatted_idents);
+        code = code.replace("method_idents", method_idents);
+        code = code.replace("validation_idents", validation_idents);
+        if !is_lexical {
+            code = code.replace("//SIG-IDENTS", "");
+        }
+        if let Some((_, terminator)) = options.recover.iter().find(|(name, _)| name == rule_name) {
+            code = code.replace("//SKIP-ON-FAILURE", "").replace("recovery_terminator", &format!("{terminator:?}.as_bytes()"));
+        }
         code = code.replace("IdentVariant", rule_name_pascal_case.as_str());
         code = code.replace("StructIdent", struct_ident.to_string().as_str());
         inner_code.push_str(code.as_str());
     }
+    // Failing inputs are parsed again from where a rule started
+    let resumed_rules = rules.iter().filter(|rule| reachable.contains(rule.name.as_str())).collect::<Vec<_>>();
+    inner_code.push_str(&multi_replace(G::pattern_resume().to_string(), vec![
+        ("RuleVariant", resumed_rules.iter().map(|rule| rule.name.clone()).collect()),
+        ("validation_idents", resumed_rules.iter().map(|rule| match lexical_rules.contains(rule.name.as_str()) {
+            true => String::new(),
+            false => String::from("&mut NoIdents"),
+        }).collect()),
+    ]));
+    if has_whitespace {
+        let rule_expr = |name: &str| rules.iter().position(|rule| rule.name == name).map(|i| &optimized_exprs[i]);
+        inner_code.push_str(&skip_whitespace_code::<G>(rule_expr("WHITESPACE"), rule_expr("COMMENT"), &lexical_rules, options));
+    }
     exprs.sort_by_key(|expr| ids.id(expr));
     exprs.dedup();
+    if options.profile_choices {
+        inner_code.push_str(&choice_profile_code::<G>(&exprs, &mut ids).replace("StructIdent", &struct_ident));
+    }
     for expr in exprs {
-        let mut new_code = code::<G>(expr, &mut ids, has_whitespace);
+        let mut new_code = code::<G>(expr, &mut ids, has_whitespace, whitespace_idents, &lexical_rules, &recovering_rules, options);
         let mut new_code2 = new_code.trim_start_matches('\n');
         let new_code2_len = new_code2.len();
         new_code2 = new_code2.trim_start_matches(' ');
@@ -180,6 +410,21 @@ pub fn gen<G: Generator>(struct_ident: String, grammar_files: Vec<String>) -> St
         inner_code.push_str(new_code.as_str());
     }
     full_code = full_code.replace("    // inner code", inner_code.as_str());
+    // Skipped text is stored as idents of an extra variant, after those of the rules
+    if !options.recover.is_empty() {
+        full_code = full_code.replace("//RECOVERING", "").replace("error_index", &ident_rules.len().to_string());
+    }
+    full_code = match options.errors {
+        ErrorMode::Detailed => full_code.replace("//DETAILED", ""),
+        ErrorMode::Offset => full_code.replace("//OFFSET", ""),
+    };
+    full_code = match options.sentinel {
+        true => full_code.replace("//SENTINEL", "").replace("sentinel_len", "1"),
+        false => full_code.replace("//BOUNDS", "").replace("sentinel_len", "0"),
+    };
     
-    full_code.parse().expect("Unable to parse code")
+    Generated {
+        code: full_code,
+        warnings,
+    }
 }
@@ -0,0 +1,15 @@
+/// Conditions with at least this many comparisons are compiled into a lookup table.
+pub const LOOKUP_TABLE_THRESHOLD: usize = 4;
+
+/// Evaluates a condition over every byte value, when it performs enough comparisons to benefit from a lookup table.
+pub fn lookup_table(comparisons: usize, condition: impl Fn(u8) -> bool) -> Option<[bool; 256]> {
+    if comparisons < LOOKUP_TABLE_THRESHOLD {
+        return None;
+    }
+
+    let mut table = [false; 256];
+    for (c, value) in table.iter_mut().enumerate() {
+        *value = condition(c as u8);
+    }
+    Some(table)
+}
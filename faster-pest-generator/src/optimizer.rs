@@ -5,21 +5,29 @@ use crate::*;
 pub enum FPestExpr {
     Ident(String),
     Str(String),
+    /// Adjacent literals of a sequence, matched with a single `starts_with`.
+    /// Failures are still reported at the start of the first literal that doesn't match.
+    FusedStr(Vec<String>),
     Insens(String),
-    CharacterCondition(String),
+    CharacterCondition(CharClass),
     NegPred(Box<FPestExpr>),
     Seq(Vec<FPestExpr>),
     Choice(Vec<FPestExpr>),
     /// true when empty is accepted
     Rep(Box<FPestExpr>, bool),
     Opt(Box<FPestExpr>),
+    /// Any bytes up to a literal, like `(!"*/" ~ ANY)*`, found with a substring search.
+    /// true when empty is accepted
+    ScanUntil(String, bool),
+    /// Between a minimum and an optional maximum number of repetitions, like `e{2, 4}`.
+    RepRange(Box<FPestExpr>, u32, Option<u32>),
 }
 
-pub fn optimize<G: Generator>(expr: &OptimizedExpr) -> FPestExpr {
+pub fn optimize(expr: &OptimizedExpr) -> FPestExpr {
     match expr {
         OptimizedExpr::Str(value) => {
             if value.len() == 1 {
-                FPestExpr::CharacterCondition(G::character(value.as_bytes()[0]))
+                FPestExpr::CharacterCondition(CharClass::byte(value.as_bytes()[0]))
             } else {
                 FPestExpr::Str(value.to_owned())
             }
@@ -29,23 +37,23 @@ pub fn optimize<G: Generator>(expr: &OptimizedExpr) -> FPestExpr {
             FPestExpr::Insens(value.to_owned())
         }
         OptimizedExpr::Ident(ident) => {
-            if let Some(condition) = G::character_ident(ident) {
-                FPestExpr::CharacterCondition(condition.to_string())
+            if let Some(class) = CharClass::builtin(ident) {
+                FPestExpr::CharacterCondition(class)
             } else {
                 FPestExpr::Ident(ident.to_owned())
             }
         },
         OptimizedExpr::NegPred(expr) => {
-            FPestExpr::NegPred(Box::new(optimize::<G>(expr)))
+            FPestExpr::NegPred(Box::new(optimize(expr)))
         }
         OptimizedExpr::Seq(first, second) => {
             if **second == OptimizedExpr::Rep(first.to_owned()) {
-                return FPestExpr::Rep(Box::new(optimize::<G>(first)), false);
+                return FPestExpr::Rep(Box::new(optimize(first)), false);
             }
 
             let mut seq = Vec::new();
             list_seq(expr, &mut seq);
-            let mut items = seq.into_iter().map(optimize::<G>).collect::<Vec<_>>();
+            let mut items = seq.into_iter().map(optimize).collect::<Vec<_>>();
 
             // Find NegPred(character condition) that are before a character condition
             // and merge them into the character condition
@@ -54,12 +62,12 @@ pub fn optimize<G: Generator>(expr: &OptimizedExpr) -> FPestExpr {
                 if let FPestExpr::NegPred(boxed) = &items[i] {
                     if let FPestExpr::CharacterCondition(c) = &**boxed {
                         if let FPestExpr::CharacterCondition(c2) = &items[i + 1] {
-                            items[i] = FPestExpr::CharacterCondition(format!("(!{} && {})", c, c2));
+                            items[i] = FPestExpr::CharacterCondition(c.complement().intersection(c2));
                             items.remove(i + 1);
                             continue;
                         } else if let FPestExpr::NegPred(boxed2) = &items[i + 1] {
                             if let FPestExpr::CharacterCondition(c2) = &**boxed2 {
-                                items[i] = FPestExpr::NegPred(Box::new(FPestExpr::CharacterCondition(format!("({} || {})", c, c2))));
+                                items[i] = FPestExpr::NegPred(Box::new(FPestExpr::CharacterCondition(c.union(c2))));
                                 items.remove(i + 1);
                                 continue;
                             }
@@ -81,24 +89,20 @@ pub fn optimize<G: Generator>(expr: &OptimizedExpr) -> FPestExpr {
             
             // Group character conditions that are next to each other
             let mut fp_choices = Vec::new();
-            let mut current_condition = String::new();
+            let mut current_condition: Option<CharClass> = None;
             for choice in choices {
-                let choice = optimize::<G>(choice);
+                let choice = optimize(choice);
                 if let FPestExpr::CharacterCondition(c) = choice {
-                    if !current_condition.is_empty() {
-                        current_condition.push_str(" || ");
-                    }
-                    current_condition.push_str(&c);
+                    current_condition = Some(current_condition.map(|current| current.union(&c)).unwrap_or(c));
                 } else {
-                    if !current_condition.is_empty() {
-                        fp_choices.push(FPestExpr::CharacterCondition(current_condition));
-                        current_condition = String::new();
+                    if let Some(current) = current_condition.take() {
+                        fp_choices.push(FPestExpr::CharacterCondition(current));
                     }
                     fp_choices.push(choice);
                 }
             }
-            if !current_condition.is_empty() {
-                fp_choices.push(FPestExpr::CharacterCondition(current_condition));
+            if let Some(current) = current_condition {
+                fp_choices.push(FPestExpr::CharacterCondition(current));
             }
 
             if fp_choices.len() == 1 {
@@ -107,15 +111,21 @@ pub fn optimize<G: Generator>(expr: &OptimizedExpr) -> FPestExpr {
                 FPestExpr::Choice(fp_choices)
             }
         },
-        OptimizedExpr::Opt(expr) => FPestExpr::Opt(Box::new(optimize::<G>(expr))),
-        OptimizedExpr::Rep(expr) => FPestExpr::Rep(Box::new(optimize::<G>(expr)), true),
+        OptimizedExpr::Opt(expr) => FPestExpr::Opt(Box::new(optimize(expr))),
+        OptimizedExpr::Rep(expr) => FPestExpr::Rep(Box::new(optimize(expr)), true),
         OptimizedExpr::Range(a, b) => {
-            if a.len() == 1 && b.len() == 1 {
-                let a = a.chars().next().expect("Range a").to_ascii_lowercase() as u8;
-                let b = b.chars().next().expect("Range b").to_ascii_lowercase() as u8;
-                FPestExpr::CharacterCondition(G::character_range(a, b))
-            } else {
-                todo!()
+            let start = a.chars().next().expect("Range start");
+            let end = b.chars().next().expect("Range end");
+
+            // Non-ASCII codepoints are matched byte by byte, one sequence per UTF-8 length
+            let mut sequences = utf8_sequences(start, end).into_iter().map(|mut sequence| match sequence.len() {
+                1 => FPestExpr::CharacterCondition(sequence.pop().expect("Range byte")),
+                _ => FPestExpr::Seq(sequence.into_iter().map(FPestExpr::CharacterCondition).collect()),
+            }).collect::<Vec<_>>();
+            match sequences.len() {
+                0 => FPestExpr::CharacterCondition(CharClass::empty()),
+                1 => sequences.pop().expect("Range sequence"),
+                _ => FPestExpr::Choice(sequences),
             }
         }
         OptimizedExpr::PosPred(_) => todo!(),
@@ -126,14 +136,15 @@ pub fn optimize<G: Generator>(expr: &OptimizedExpr) -> FPestExpr {
     }
 }
 
-pub fn optimize_second_stage(expr: &mut FPestExpr, character_set_rules: &HashMap<&str, String>) {
+pub fn optimize_second_stage(expr: &mut FPestExpr, character_set_rules: &HashMap<&str, CharClass>) {
     match expr {
         FPestExpr::Ident(ident) => if let Some(condition) = character_set_rules.get(ident.as_str()) {
-            *expr = FPestExpr::CharacterCondition(condition.to_string());
+            *expr = FPestExpr::CharacterCondition(*condition);
         },
-        FPestExpr::Str(_) => (),
+        FPestExpr::Str(_) | FPestExpr::FusedStr(_) => (),
         FPestExpr::Insens(_) => (),
         FPestExpr::CharacterCondition(_) => (),
+        FPestExpr::ScanUntil(_, _) => (),
         FPestExpr::NegPred(expr) => optimize_second_stage(expr, character_set_rules),
         FPestExpr::Seq(items) => {
             for item in items.iter_mut() {
@@ -147,12 +158,12 @@ pub fn optimize_second_stage(expr: &mut FPestExpr, character_set_rules: &HashMap
                 if let FPestExpr::NegPred(boxed) = &items[i] {
                     if let FPestExpr::CharacterCondition(c) = &**boxed {
                         if let FPestExpr::CharacterCondition(c2) = &items[i + 1] {
-                            items[i] = FPestExpr::CharacterCondition(format!("(!{} && {})", c, c2));
+                            items[i] = FPestExpr::CharacterCondition(c.complement().intersection(c2));
                             items.remove(i + 1);
                             continue;
                         } else if let FPestExpr::NegPred(boxed2) = &items[i + 1] {
                             if let FPestExpr::CharacterCondition(c2) = &**boxed2 {
-                                items[i] = FPestExpr::NegPred(Box::new(FPestExpr::CharacterCondition(format!("({} || {})", c, c2))));
+                                items[i] = FPestExpr::NegPred(Box::new(FPestExpr::CharacterCondition(c.union(c2))));
                                 items.remove(i + 1);
                                 continue;
                             }
@@ -169,24 +180,20 @@ pub fn optimize_second_stage(expr: &mut FPestExpr, character_set_rules: &HashMap
         FPestExpr::Choice(items) => {
             // Group character conditions that are next to each other
             let mut fp_choices = Vec::new();
-            let mut current_condition = String::new();
+            let mut current_condition: Option<CharClass> = None;
             for item in items.iter_mut() {
                 optimize_second_stage(item, character_set_rules);
                 if let FPestExpr::CharacterCondition(c) = item {
-                    if !current_condition.is_empty() {
-                        current_condition.push_str(" || ");
-                    }
-                    current_condition.push_str(c);
+                    current_condition = Some(current_condition.map(|current| current.union(c)).unwrap_or(*c));
                 } else {
-                    if !current_condition.is_empty() {
-                        fp_choices.push(FPestExpr::CharacterCondition(current_condition));
-                        current_condition = String::new();
+                    if let Some(current) = current_condition.take() {
+                        fp_choices.push(FPestExpr::CharacterCondition(current));
                     }
                     fp_choices.push(item.to_owned());
                 }
             }
-            if !current_condition.is_empty() {
-                fp_choices.push(FPestExpr::CharacterCondition(current_condition));
+            if let Some(current) = current_condition {
+                fp_choices.push(FPestExpr::CharacterCondition(current));
             }
 
             if fp_choices.len() == 1 {
@@ -195,7 +202,179 @@ pub fn optimize_second_stage(expr: &mut FPestExpr, character_set_rules: &HashMap
                 *expr = FPestExpr::Choice(fp_choices);
             }
         },
-        FPestExpr::Rep(expr, _) => optimize_second_stage(expr, character_set_rules),
-        FPestExpr::Opt(expr) => optimize_second_stage(expr, character_set_rules),
+        FPestExpr::Rep(inner, empty_accepted) => {
+            optimize_second_stage(inner, character_set_rules);
+
+            // Find repetitions of any character that isn't the start of a literal
+            // and turn them into a search for the literal
+            if let FPestExpr::Seq(items) = &**inner {
+                if let [FPestExpr::NegPred(negated), FPestExpr::CharacterCondition(c)] = items.as_slice() {
+                    if let (FPestExpr::Str(literal), true) = (&**negated, c.is_any()) {
+                        *expr = FPestExpr::ScanUntil(literal.to_owned(), *empty_accepted);
+                    }
+                }
+            }
+        }
+        FPestExpr::Opt(expr) | FPestExpr::RepRange(expr, _, _) => optimize_second_stage(expr, character_set_rules),
+    }
+}
+
+/// Puts back the counted repetitions that were replaced by placeholders in [`get_all_rules`].
+/// Bounds that have a dedicated expression, like `e{0, 1}`, are turned into it.
+pub fn restore_counted_repetitions(expr: &mut FPestExpr, counted: &CountedRepetitions) {
+    match expr {
+        FPestExpr::Ident(ident) => if let Some((inner, min, max)) = counted.get(ident.as_str()) {
+            let mut inner = optimize(inner);
+            restore_counted_repetitions(&mut inner, counted);
+            *expr = match (min, max) {
+                (0, None) => FPestExpr::Rep(Box::new(inner), true),
+                (1, None) => FPestExpr::Rep(Box::new(inner), false),
+                (0, Some(1)) => FPestExpr::Opt(Box::new(inner)),
+                (1, Some(1)) => inner,
+                _ => FPestExpr::RepRange(Box::new(inner), *min, *max),
+            };
+        },
+        FPestExpr::Str(_) | FPestExpr::FusedStr(_) | FPestExpr::Insens(_) | FPestExpr::CharacterCondition(_) | FPestExpr::ScanUntil(_, _) => (),
+        FPestExpr::NegPred(expr) | FPestExpr::Rep(expr, _) | FPestExpr::Opt(expr) | FPestExpr::RepRange(expr, _, _) => restore_counted_repetitions(expr, counted),
+        FPestExpr::Seq(items) | FPestExpr::Choice(items) => items.iter_mut().for_each(|item| restore_counted_repetitions(item, counted)),
+    }
+}
+
+/// Returns the literal text matched by an expression, if it matches a fixed string.
+fn as_literals(expr: &FPestExpr) -> Option<Vec<String>> {
+    match expr {
+        FPestExpr::Str(value) => Some(vec![value.to_owned()]),
+        FPestExpr::FusedStr(values) => Some(values.to_owned()),
+        FPestExpr::CharacterCondition(c) => c.single_byte().filter(u8::is_ascii).map(|c| vec![(c as char).to_string()]),
+        _ => None,
+    }
+}
+
+/// Merges adjacent literals of sequences into a single [`FPestExpr::FusedStr`], so that they are matched with one `starts_with`.
+/// This must not be used when WHITESPACE is injected between sequence items.
+pub fn fuse_literals(expr: &mut FPestExpr) {
+    match expr {
+        FPestExpr::Ident(_) | FPestExpr::Str(_) | FPestExpr::FusedStr(_) | FPestExpr::Insens(_) | FPestExpr::CharacterCondition(_) | FPestExpr::ScanUntil(_, _) => (),
+        FPestExpr::NegPred(expr) | FPestExpr::Rep(expr, _) | FPestExpr::Opt(expr) | FPestExpr::RepRange(expr, _, _) => fuse_literals(expr),
+        FPestExpr::Choice(items) => items.iter_mut().for_each(fuse_literals),
+        FPestExpr::Seq(items) => {
+            items.iter_mut().for_each(fuse_literals);
+
+            let mut i = 0;
+            while i + 1 < items.len() {
+                if let (Some(mut first), Some(second)) = (as_literals(&items[i]), as_literals(&items[i + 1])) {
+                    first.extend(second);
+                    items[i] = FPestExpr::FusedStr(first);
+                    items.remove(i + 1);
+                    continue;
+                }
+                i += 1;
+            }
+
+            if items.len() == 1 {
+                *expr = items.pop().expect("Seq")
+            }
+        }
+    }
+}
+
+/// Reorders the alternatives of choices by decreasing expected frequency.
+/// Two neighboring alternatives are only swapped when their FIRST sets prove that at most one of them can match, so the result of parsing never changes.
+pub fn reorder_choices(expr: &mut FPestExpr, rules: &HashMap<&str, FPestExpr>, frequency: &impl Fn(&FPestExpr, CharClass) -> usize) {
+    match expr {
+        FPestExpr::Ident(_) | FPestExpr::Str(_) | FPestExpr::FusedStr(_) | FPestExpr::Insens(_) | FPestExpr::CharacterCondition(_) | FPestExpr::ScanUntil(_, _) => (),
+        FPestExpr::NegPred(expr) | FPestExpr::Rep(expr, _) | FPestExpr::Opt(expr) | FPestExpr::RepRange(expr, _, _) => reorder_choices(expr, rules, frequency),
+        FPestExpr::Seq(items) => items.iter_mut().for_each(|item| reorder_choices(item, rules, frequency)),
+        FPestExpr::Choice(items) => {
+            // Profiles refer to alternatives as they were, before their own choices were reordered
+            let mut items_with_info = items.drain(..).map(|mut item| {
+                let (first, nullable) = first_set(&item, rules, &mut Vec::new());
+                let item_frequency = frequency(&item, first);
+                reorder_choices(&mut item, rules, frequency);
+                (item, first, nullable, item_frequency)
+            }).collect::<Vec<_>>();
+
+            // Bubble sort that only swaps neighbors that can't both match
+            let mut swapped = true;
+            while swapped {
+                swapped = false;
+                for i in 0..items_with_info.len().saturating_sub(1) {
+                    let (_, first1, nullable1, frequency1) = &items_with_info[i];
+                    let (_, first2, nullable2, frequency2) = &items_with_info[i + 1];
+                    if frequency2 > frequency1 && !nullable1 && !nullable2 && first1.is_disjoint(first2) {
+                        items_with_info.swap(i, i + 1);
+                        swapped = true;
+                    }
+                }
+            }
+
+            items.extend(items_with_info.into_iter().map(|(item, _, _, _)| item));
+        }
+    }
+}
+
+/// Silent rules with at most this many nodes are inlined in all their callers.
+/// Larger silent rules are only inlined when they are used once.
+pub const INLINE_THRESHOLD: usize = 12;
+
+/// Replaces references to inlinable silent rules by their expression, so that the optimizer can work across rule boundaries.
+/// Inlining never changes the generated code size much, since identical expressions share the same function.
+pub fn inline_silent_rules(expr: &mut FPestExpr, inlinable: &HashMap<&str, FPestExpr>, expanding: &mut Vec<String>) {
+    match expr {
+        FPestExpr::Ident(ident) => {
+            if let Some(body) = inlinable.get(ident.as_str()) {
+                if !expanding.contains(ident) {
+                    let mut body = body.to_owned();
+                    expanding.push(ident.to_owned());
+                    inline_silent_rules(&mut body, inlinable, expanding);
+                    expanding.pop();
+                    *expr = body;
+                }
+            }
+        }
+        FPestExpr::Str(_) | FPestExpr::FusedStr(_) | FPestExpr::Insens(_) | FPestExpr::CharacterCondition(_) | FPestExpr::ScanUntil(_, _) => (),
+        FPestExpr::NegPred(expr) | FPestExpr::Rep(expr, _) | FPestExpr::Opt(expr) | FPestExpr::RepRange(expr, _, _) => inline_silent_rules(expr, inlinable, expanding),
+        FPestExpr::Seq(items) => {
+            // Sequences are associative, so nested ones can be flattened
+            for mut item in std::mem::take(items) {
+                inline_silent_rules(&mut item, inlinable, expanding);
+                match item {
+                    FPestExpr::Seq(inner_items) => items.extend(inner_items),
+                    item => items.push(item),
+                }
+            }
+        }
+        FPestExpr::Choice(items) => {
+            // So are ordered choices
+            for mut item in std::mem::take(items) {
+                inline_silent_rules(&mut item, inlinable, expanding);
+                match item {
+                    FPestExpr::Choice(inner_items) => items.extend(inner_items),
+                    item => items.push(item),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn str(value: &str) -> FPestExpr {
+        FPestExpr::Str(value.to_string())
+    }
+
+    #[test]
+    fn alternatives_that_can_both_match_are_never_swapped() {
+        // "ac" is more frequent than "ab", but both start with 'a', so only "x" can move ahead of them
+        let frequency = |item: &FPestExpr, _first: CharClass| match item {
+            FPestExpr::Str(value) if value == "ab" => 0,
+            FPestExpr::Str(value) if value == "ac" => 5,
+            _ => 10,
+        };
+        let mut expr = FPestExpr::Choice(vec![str("ab"), str("ac"), str("x")]);
+        reorder_choices(&mut expr, &HashMap::new(), &frequency);
+        assert_eq!(expr, FPestExpr::Choice(vec![str("x"), str("ab"), str("ac")]));
     }
 }
@@ -3,28 +3,147 @@ use crate::*;
 pub fn list_exprs(expr: &FPestExpr) -> Vec<&FPestExpr> {
     let mut exprs = Vec::new();
     match expr {
-        FPestExpr::NegPred(expr) | FPestExpr::Opt(expr) | FPestExpr::Rep(expr, _) => exprs.extend(list_exprs(expr)),
+        FPestExpr::NegPred(expr) | FPestExpr::Opt(expr) | FPestExpr::Rep(expr, _) | FPestExpr::RepRange(expr, _, _) => exprs.extend(list_exprs(expr)),
         FPestExpr::Seq(items) | FPestExpr::Choice(items) => items.iter().for_each(|i| exprs.extend(list_exprs(i))),
-        FPestExpr::Ident(_) | FPestExpr::Str(_) | FPestExpr::Insens(_) | FPestExpr::CharacterCondition(_) => {},
+        FPestExpr::Ident(_) | FPestExpr::Str(_) | FPestExpr::FusedStr(_) | FPestExpr::Insens(_) | FPestExpr::CharacterCondition(_) | FPestExpr::ScanUntil(_, _) => {},
     }
     exprs.push(expr);
     exprs
 }
 
-pub fn contains_idents(expr: &FPestExpr, has_whitespace: bool) -> bool {
+/// Lists the rules and builtins an expression refers to.
+pub fn list_idents(expr: &FPestExpr) -> Vec<&str> {
+    list_exprs(expr).into_iter().filter_map(|expr| match expr {
+        FPestExpr::Ident(ident) => Some(ident.as_str()),
+        _ => None,
+    }).collect()
+}
+
+/// Lists the rules and builtins a grammar expression refers to, before any optimization.
+pub fn list_grammar_idents(expr: &OptimizedExpr) -> Vec<&str> {
+    match expr {
+        OptimizedExpr::Ident(ident) => vec![ident.as_str()],
+        OptimizedExpr::Seq(first, second) | OptimizedExpr::Choice(first, second) => {
+            let mut idents = list_grammar_idents(first);
+            idents.extend(list_grammar_idents(second));
+            idents
+        }
+        OptimizedExpr::PosPred(expr) | OptimizedExpr::NegPred(expr) | OptimizedExpr::Opt(expr) | OptimizedExpr::Rep(expr)
+            | OptimizedExpr::Push(expr) | OptimizedExpr::RestoreOnErr(expr) => list_grammar_idents(expr),
+        _ => Vec::new(),
+    }
+}
+
+/// Lists the rules that can be reached from `roots`, given a function listing the references of a rule.
+pub fn reachable_rules<'a>(roots: Vec<&'a str>, references: impl Fn(&str) -> Vec<&'a str>) -> HashSet<&'a str> {
+    let mut reachable = HashSet::new();
+    let mut to_visit = roots;
+    while let Some(rule) = to_visit.pop() {
+        if reachable.insert(rule) {
+            to_visit.extend(references(rule));
+        }
+    }
+    reachable
+}
+
+/// Tells whether an expression refers to a rule that recovers, directly or through the rules it refers to.
+pub fn can_recover(expr: &FPestExpr, recovering_rules: &HashSet<String>) -> bool {
+    list_idents(expr).into_iter().any(|ident| recovering_rules.contains(ident))
+}
+
+/// Computes the bytes an expression can start with, and whether it can succeed without consuming anything.
+/// The result is conservative: unknown identifiers can start with anything and match empty.
+pub fn first_set(expr: &FPestExpr, rules: &HashMap<&str, FPestExpr>, visiting: &mut Vec<String>) -> (CharClass, bool) {
+    match expr {
+        FPestExpr::Ident(ident) if ident == "EOI" || ident == "SOI" => (CharClass::empty(), true),
+        FPestExpr::Ident(ident) if ident == "NEWLINE" => (CharClass::byte(b'\n').union(&CharClass::byte(b'\r')), false),
+        FPestExpr::Ident(ident) => match rules.get(ident.as_str()) {
+            Some(expr) if !visiting.contains(ident) => {
+                visiting.push(ident.to_owned());
+                let first = first_set(expr, rules, visiting);
+                visiting.pop();
+                first
+            }
+            _ => (CharClass::any(), true),
+        },
+        FPestExpr::Str(value) | FPestExpr::Insens(value) => match value.as_bytes().first() {
+            Some(c) if matches!(expr, FPestExpr::Insens(_)) => (CharClass::byte(c.to_ascii_lowercase()).union(&CharClass::byte(c.to_ascii_uppercase())), false),
+            Some(c) => (CharClass::byte(*c), false),
+            None => (CharClass::empty(), true),
+        },
+        FPestExpr::FusedStr(values) => first_set(&FPestExpr::Str(values.concat()), rules, visiting),
+        FPestExpr::CharacterCondition(class) => (*class, false),
+        FPestExpr::ScanUntil(_, empty_accepted) => (CharClass::any(), *empty_accepted),
+        FPestExpr::NegPred(_) => (CharClass::empty(), true),
+        FPestExpr::Seq(items) => {
+            let mut first = CharClass::empty();
+            for item in items {
+                let (item_first, nullable) = first_set(item, rules, visiting);
+                first = first.union(&item_first);
+                if !nullable {
+                    return (first, false);
+                }
+            }
+            (first, true)
+        }
+        FPestExpr::Choice(items) => items.iter().fold((CharClass::empty(), false), |(first, nullable), item| {
+            let (item_first, item_nullable) = first_set(item, rules, visiting);
+            (first.union(&item_first), nullable || item_nullable)
+        }),
+        FPestExpr::Opt(expr) | FPestExpr::Rep(expr, true) => (first_set(expr, rules, visiting).0, true),
+        FPestExpr::Rep(expr, false) => first_set(expr, rules, visiting),
+        FPestExpr::RepRange(expr, min, _) => {
+            let (first, nullable) = first_set(expr, rules, visiting);
+            (first, nullable || *min == 0)
+        }
+    }
+}
+
+/// Tells whether parsing an expression can push idents.
+/// Silent rules in `lexical_rules` are known not to push any, so referencing them doesn't count.
+/// When `whitespace_idents` is set, skipping implicit whitespace can push idents too, which sequences and repetitions do.
+pub fn contains_idents(expr: &FPestExpr, lexical_rules: &HashSet<String>, whitespace_idents: bool) -> bool {
     match expr {
         FPestExpr::Ident(ident) if ident != "SOI" && ident != "EOI" && ident != "NEWLINE" => {
-            true
+            !lexical_rules.contains(ident)
         },
-        FPestExpr::NegPred(expr) | FPestExpr::Opt(expr) => contains_idents(expr, has_whitespace),
-        FPestExpr::Seq(items) => has_whitespace || items.iter().any(|i| contains_idents(i, has_whitespace)),
-        FPestExpr::Choice(items) => true, // TODO: items.iter().any(|i| contains_idents(i, has_whitespace)),
-        FPestExpr::Rep(expr, _) => has_whitespace || contains_idents(expr, has_whitespace),
-        FPestExpr::Str(_) | FPestExpr::Insens(_) | FPestExpr::CharacterCondition(_) => false,
+        FPestExpr::NegPred(expr) | FPestExpr::Opt(expr) => contains_idents(expr, lexical_rules, whitespace_idents),
+        FPestExpr::Seq(_) if whitespace_idents => true,
+        FPestExpr::Rep(expr, _) | FPestExpr::RepRange(expr, _, _) if whitespace_idents && !matches!(**expr, FPestExpr::CharacterCondition(_)) => true,
+        FPestExpr::Seq(items) | FPestExpr::Choice(items) => items.iter().any(|item| contains_idents(item, lexical_rules, whitespace_idents)),
+        FPestExpr::Rep(expr, _) | FPestExpr::RepRange(expr, _, _) => contains_idents(expr, lexical_rules, whitespace_idents),
+        FPestExpr::Str(_) | FPestExpr::FusedStr(_) | FPestExpr::Insens(_) | FPestExpr::CharacterCondition(_) | FPestExpr::ScanUntil(_, _) => false,
         FPestExpr::Ident(_) => false,
     }
 }
 
+/// Tells whether skipping implicit whitespace can push idents, which is when one of `whitespace_rules` isn't lexical.
+pub fn whitespace_idents(whitespace_rules: &[&str], lexical_rules: &HashSet<String>) -> bool {
+    whitespace_rules.iter().any(|name| !lexical_rules.contains(*name))
+}
+
+/// Finds the silent rules that can never push idents, not even through the silent rules they refer to.
+/// `whitespace_rules` are the WHITESPACE and COMMENT rules of the grammar, that can push idents when skipping implicit whitespace.
+pub fn lexical_rules<'a>(silent_rules: impl Iterator<Item = (&'a str, &'a FPestExpr)>, whitespace_rules: &[&str]) -> HashSet<String> {
+    let silent_rules = silent_rules.collect::<Vec<_>>();
+
+    // Start by assuming all of them are lexical, then remove those that can reach a non-lexical rule
+    let mut lexical_rules = silent_rules.iter().map(|(name, _)| name.to_string()).collect::<HashSet<_>>();
+    loop {
+        let whitespace_idents = whitespace_idents(whitespace_rules, &lexical_rules);
+        let pushing = silent_rules.iter()
+            .filter(|(name, expr)| lexical_rules.contains(*name) && contains_idents(expr, &lexical_rules, whitespace_idents))
+            .map(|(name, _)| name.to_string())
+            .collect::<Vec<_>>();
+        if pushing.is_empty() {
+            return lexical_rules;
+        }
+        for name in pushing {
+            lexical_rules.remove(&name);
+        }
+    }
+}
+
 pub fn list_choices<'a>(expr: &'a OptimizedExpr, choices: &mut Vec<&'a OptimizedExpr>) {
     if let OptimizedExpr::Choice(first, second) = expr {
         list_choices(first, choices);
@@ -42,3 +161,50 @@ pub fn list_seq<'a>(expr: &'a OptimizedExpr, seq: &mut Vec<&'a OptimizedExpr>) {
         seq.push(expr);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn str(value: &str) -> FPestExpr {
+        FPestExpr::Str(value.to_string())
+    }
+
+    fn ident(name: &str) -> FPestExpr {
+        FPestExpr::Ident(name.to_string())
+    }
+
+    #[test]
+    fn sequences_stop_at_the_first_item_that_consumes() {
+        let rules = HashMap::new();
+        let expr = FPestExpr::Seq(vec![FPestExpr::Opt(Box::new(str("-"))), str("1"), str("x")]);
+        let (first, nullable) = first_set(&expr, &rules, &mut Vec::new());
+        assert_eq!(first, CharClass::byte(b'-').union(&CharClass::byte(b'1')));
+        assert!(!nullable);
+    }
+
+    #[test]
+    fn choices_are_nullable_if_an_alternative_is() {
+        let rules = HashMap::new();
+        let expr = FPestExpr::Choice(vec![FPestExpr::Insens("a".to_string()), FPestExpr::Rep(Box::new(str("b")), true)]);
+        let (first, nullable) = first_set(&expr, &rules, &mut Vec::new());
+        assert_eq!(first, CharClass::byte(b'a').union(&CharClass::byte(b'A')).union(&CharClass::byte(b'b')));
+        assert!(nullable);
+    }
+
+    #[test]
+    fn rules_are_followed_and_recursion_is_conservative() {
+        let mut rules = HashMap::new();
+        rules.insert("list", FPestExpr::Seq(vec![str("["), FPestExpr::Opt(Box::new(ident("list"))), str("]")]));
+        rules.insert("value", FPestExpr::Choice(vec![ident("list"), str("0")]));
+        let (first, nullable) = first_set(&ident("value"), &rules, &mut Vec::new());
+        assert_eq!(first, CharClass::byte(b'[').union(&CharClass::byte(b'0')));
+        assert!(!nullable);
+
+        let mut rules = HashMap::new();
+        rules.insert("left", FPestExpr::Seq(vec![ident("left"), str("+")]));
+        let (first, nullable) = first_set(&ident("left"), &rules, &mut Vec::new());
+        assert!(first.is_any());
+        assert!(!nullable);
+    }
+}
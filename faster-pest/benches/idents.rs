@@ -0,0 +1,99 @@
+#![feature(test)]
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+extern crate test;
+
+use faster_pest::*;
+use test::Bencher;
+
+/// Counts allocated bytes, so that benches can report how much memory walking idents takes.
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let allocated = ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+        PEAK.fetch_max(allocated, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Bytes allocated at the peak of `f`, on top of what was allocated before.
+/// Benches print it, which `cargo +nightly bench --bench idents -- --nocapture` shows.
+fn peak_memory(f: impl FnOnce()) -> usize {
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    PEAK.store(before, Ordering::Relaxed);
+    f();
+    PEAK.load(Ordering::Relaxed) - before
+}
+
+#[derive(Parser)]
+#[grammar = "faster-pest/examples/json/grammar.pest"]
+pub struct JsonParser {
+
+}
+
+fn read_input() -> String {
+    match std::fs::read_to_string("faster-pest/examples/json/input.json") {
+        Ok(s) => s,
+        Err(_) => match std::fs::read_to_string("examples/json/input.json") {
+            Ok(s) => s,
+            Err(e) => panic!("cannot read file: {}", e)
+        }
+    }
+}
+
+fn walk_rules(ident: IdentRef<'_, Ident<'_>>) {
+    black_box(ident.as_rule());
+    ident.children().for_each(walk_rules);
+}
+
+fn walk_idents(ident: IdentRef<'_, Ident<'_>>) {
+    black_box(ident.ident());
+    ident.children().for_each(walk_idents);
+}
+
+/// Entries are resolved one at a time, so nothing is allocated besides them.
+#[bench]
+fn json_walk_rules(b: &mut Bencher) {
+    let unparsed_file = read_input();
+    let peak = peak_memory(|| {
+        let output = JsonParser::parse_file(&unparsed_file).expect("unsuccessful parse");
+        walk_rules(output.root());
+    });
+    eprintln!("json_walk_rules: {peak} bytes at peak");
+
+    b.iter(|| {
+        let output = JsonParser::parse_file(&unparsed_file).expect("unsuccessful parse");
+        walk_rules(output.root());
+    });
+}
+
+/// The first call to `ident` resolves every entry at once, so that it can hand out references.
+#[bench]
+fn json_walk_idents(b: &mut Bencher) {
+    let unparsed_file = read_input();
+    let peak = peak_memory(|| {
+        let output = JsonParser::parse_file(&unparsed_file).expect("unsuccessful parse");
+        walk_idents(output.root());
+    });
+    eprintln!("json_walk_idents: {peak} bytes at peak");
+
+    b.iter(|| {
+        let output = JsonParser::parse_file(&unparsed_file).expect("unsuccessful parse");
+        walk_idents(output.root());
+    });
+}
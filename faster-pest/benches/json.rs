@@ -4,6 +4,7 @@ use std::{borrow::Cow, collections::BTreeMap};
 
 extern crate test;
 
+#[allow(dead_code)]
 enum Value<'i> {
     String(Cow<'i, str>),
     Number(f64),
@@ -13,7 +14,8 @@ enum Value<'i> {
     Null,
 }
 
-fn unescape_str(s: &str) -> Cow<str> {
+#[allow(dead_code)]
+fn unescape_str(s: &str) -> Cow<'_, str> {
     let bytes = s.as_bytes();
     let mut i = 0;
     while i < s.len() {
@@ -153,9 +155,7 @@ mod faster_pest_json {
             }
         };
 
-        b.iter(|| black_box({
-            JsonParser::parse_file(&unparsed_file).expect("unsuccessful parse");
-        }));
+        b.iter(|| black_box(JsonParser::parse_file(&unparsed_file).expect("unsuccessful parse")));
     }
 
     #[bench]
@@ -168,12 +168,52 @@ mod faster_pest_json {
             }
         };
 
-        b.iter(|| black_box({
+        b.iter(|| {
             let output = JsonParser::parse_file(&unparsed_file).map_err(|e| e.print(unparsed_file.as_str())).expect("unsuccessful parse");
             let file = output.into_iter().next().expect("couldn't find file rule");
             let main_object = file.children().next().expect("couldn't find main object");
-            let output = Value::from_ident_ref(main_object);
-        }));
+            black_box(Value::from_ident_ref(main_object));
+        });
+    }
+}
+
+mod faster_pest_sentinel {
+    use std::hint::black_box;
+    use faster_pest::*;
+    use test::Bencher;
+
+    #[derive(Parser)]
+    #[grammar = "faster-pest/examples/json/grammar.pest"]
+    #[faster_pest(sentinel)]
+    pub struct JsonParser {
+    
+    }
+
+    #[bench]
+    fn json_as_is(b: &mut Bencher) {
+        let mut unparsed_file = match std::fs::read_to_string("faster-pest/examples/json/input.json") {
+            Ok(s) => s,
+            Err(_) => match std::fs::read_to_string("examples/json/input.json") {
+                Ok(s) => s,
+                Err(e) => panic!("cannot read file: {}", e)
+            }
+        };
+        unparsed_file.push('\0');
+
+        b.iter(|| black_box(JsonParser::parse_file_padded(&unparsed_file).expect("unsuccessful parse")));
+    }
+
+    #[bench]
+    fn json_as_is_copied(b: &mut Bencher) {
+        let unparsed_file = match std::fs::read_to_string("faster-pest/examples/json/input.json") {
+            Ok(s) => s,
+            Err(_) => match std::fs::read_to_string("examples/json/input.json") {
+                Ok(s) => s,
+                Err(e) => panic!("cannot read file: {}", e)
+            }
+        };
+
+        b.iter(|| black_box(JsonParser::parse_file(&unparsed_file).expect("unsuccessful parse")));
     }
 }
 
@@ -190,8 +230,6 @@ mod serde {
             }
         };
 
-        b.iter(|| black_box({
-            serde_json::from_str::<serde_json::Value>(&unparsed_file).expect("unsuccessful parse");
-        }));
+        b.iter(|| black_box(serde_json::from_str::<serde_json::Value>(&unparsed_file).expect("unsuccessful parse")));
     }
 }
@@ -5,9 +5,9 @@ use std::hint::black_box;
 extern crate test;
 
 #[path = "../examples/lightgrep/main.rs"]
+#[allow(dead_code)]
 mod lightgrep;
 
-use faster_pest::*;
 use test::Bencher;
 use lightgrep::*;
 
@@ -21,9 +21,7 @@ fn lightgrep_as_is(b: &mut Bencher) {
         }
     };
 
-    b.iter(|| black_box({
-        LightgrepParser::parse_file(&unparsed_file).expect("unsuccessful parse");
-    }));
+    b.iter(|| black_box(LightgrepParser::parse_file(&unparsed_file).expect("unsuccessful parse")));
 }
 
 #[bench]
@@ -36,10 +34,10 @@ fn lightgrep_to_rust(b: &mut Bencher) {
         }
     };
 
-    b.iter(|| black_box({
+    b.iter(|| {
         let output = LightgrepParser::parse_file(&unparsed_file).map_err(|e| e.print(unparsed_file.as_str())).expect("unsuccessful parse");
         let file = output.into_iter().next().expect("couldn't find file rule");
         let main_object = file.children().next().expect("couldn't find main object");
-        let output = ExpressionRationnelle::from_ident_ref(main_object);
-    }));
+        black_box(ExpressionRationnelle::from_ident_ref(main_object));
+    });
 }
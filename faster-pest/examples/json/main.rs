@@ -6,6 +6,7 @@ use std::{collections::HashMap, borrow::Cow};
 pub struct JsonParser;
 
 #[derive(Debug)]
+#[allow(dead_code)]
 enum Value<'i> {
     String(Cow<'i, str>),
     Number(f64),
@@ -1,5 +1,4 @@
 use faster_pest::*;
-use std::collections::HashMap;
 
 #[derive(Parser)]
 #[grammar = "faster-pest/examples/po/grammar.pest"]
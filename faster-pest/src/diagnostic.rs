@@ -0,0 +1,75 @@
+use miette::{Diagnostic, LabeledSpan, NamedSource, Severity, SourceCode};
+use crate::*;
+
+impl Error {
+    /// Attaches the input the error was found in, so that miette shows where it is and the trace that led there.
+    /// It is meant to be called last, since the trace is copied as it is.
+    pub fn with_source_code(mut self, file_name: impl AsRef<str>, input: impl Into<String>) -> Self {
+        self.source = Some(Box::new(AttachedSource {
+            code: NamedSource::new(file_name, input.into()),
+            trace: self.trace().iter().cloned().map(TraceFrame).collect(),
+        }));
+        self
+    }
+}
+
+/// What [`Error::with_source_code`] attaches to an error.
+#[derive(Debug)]
+pub(crate) struct AttachedSource {
+    code: NamedSource<String>,
+    /// Frames of the trace, which miette needs as diagnostics of their own.
+    trace: Vec<TraceFrame>,
+}
+
+/// An expression of the trace of an error, that miette shows as related information.
+#[derive(Debug)]
+struct TraceFrame(String);
+
+impl std::fmt::Display for TraceFrame {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "in {}", self.0)
+    }
+}
+
+impl std::error::Error for TraceFrame {}
+
+impl Diagnostic for TraceFrame {
+    fn severity(&self) -> Option<Severity> {
+        Some(Severity::Advice)
+    }
+}
+
+/// Errors only have labels and related information when their input was attached with [`Error::with_source_code`].
+impl Diagnostic for Error {
+    fn help<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+        self.note().map(|note| Box::new(note) as Box<dyn std::fmt::Display>)
+    }
+
+    fn source_code(&self) -> Option<&dyn SourceCode> {
+        self.source.as_deref().map(|source| &source.code as &dyn SourceCode)
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        let input = self.source.as_ref()?.code.inner().as_str();
+        let offset = self.offset(input);
+        let span = self.span(input);
+
+        let found = match input[offset..].chars().next() {
+            Some(c) => format!("unexpected {c:?}"),
+            None => String::from("unexpected end of input"),
+        };
+        let mut labels = vec![LabeledSpan::new_primary_with_span(Some(found), offset..span.end)];
+        if span.start < offset {
+            labels.push(LabeledSpan::new_with_span(Some(String::from("while parsing this")), span.start..offset));
+        }
+        Some(Box::new(labels.into_iter()))
+    }
+
+    fn related<'a>(&'a self) -> Option<Box<dyn Iterator<Item = &'a dyn Diagnostic> + 'a>> {
+        let trace = &self.source.as_ref()?.trace;
+        match trace.is_empty() {
+            true => None,
+            false => Some(Box::new(trace.iter().map(|frame| frame as &dyn Diagnostic))),
+        }
+    }
+}
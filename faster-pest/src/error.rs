@@ -1,23 +1,92 @@
+use std::cell::{Cell, RefCell};
+use crate::recovery::{is_recovering, record_recovered};
+use crate::profile::parsing_again;
+
 const RED: &str = "\x1b[31;1m";
 const NORMAL: &str = "\x1b[0m";
 const BLUE: &str = "\x1b[34;1m";
 const BOLD: &str = "\x1b[1m";
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ErrorKind {
+    /// A literal was expected.
     ExpectedValue(&'static str),
+    /// Something described by the text was expected, like a character class.
     Expected(&'static str),
+    /// A rule was expected.
+    ExpectedRule(&'static str),
+    /// A negative predicate matched. It holds the negated rule, or the negated expression in pest syntax.
     NegPredFailed(&'static str),
-    All(Vec<Error>)
+    /// Alternatives of choices that failed at the same furthest position, without duplicates.
+    /// They are never [`ErrorKind::ExpectedOneOf`] themselves.
+    /// It is empty for failures that come from no expression in particular, which display as `unexpected input`.
+    ExpectedOneOf(Vec<ErrorKind>),
+}
+
+impl ErrorKind {
+    /// Describes what was expected, for lists of alternatives.
+    fn describe(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ErrorKind::ExpectedValue(expected) => write!(f, "`{expected}`"),
+            ErrorKind::Expected(expected) | ErrorKind::ExpectedRule(expected) => write!(f, "{expected}"),
+            ErrorKind::NegPredFailed(not_expected) => write!(f, "anything but {not_expected}"),
+            ErrorKind::ExpectedOneOf(kinds) => {
+                for (i, kind) in kinds.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    kind.describe(f)?;
+                }
+                Ok(())
+            }
+        }
+    }
 }
 
 impl std::fmt::Display for ErrorKind {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
-            ErrorKind::ExpectedValue(expected) => write!(f, "Expected value: {expected}"),
-            ErrorKind::Expected(expected) => write!(f, "Expected: {expected}"),
-            ErrorKind::NegPredFailed(not_expected) => write!(f, "Negated predicate failed: {not_expected}"),
-            ErrorKind::All(errors) => write!(f, "All {} accepted patterns fail to match", errors.len()),
+            // Failures that come from no expression in particular, like choices without alternatives
+            ErrorKind::ExpectedOneOf(kinds) if kinds.is_empty() => write!(f, "unexpected input"),
+            ErrorKind::ExpectedOneOf(_) => {
+                write!(f, "expected one of ")?;
+                self.describe(f)
+            }
+            _ => {
+                write!(f, "expected ")?;
+                self.describe(f)
+            }
+        }
+    }
+}
+
+/// Position of an error in its input, with a line and columns that start at 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineCol {
+    pub line: usize,
+    /// Column counted in bytes.
+    pub col_utf8: usize,
+    /// Column counted in UTF-16 code units, as editors using the Language Server Protocol expect.
+    pub col_utf16: usize,
+}
+
+/// How [`Error::render`] formats errors.
+#[derive(Debug, Clone, Copy)]
+pub struct RenderOptions<'a> {
+    /// Name of the parsed file, shown with the line and column. Defaults to `input`.
+    pub file_name: Option<&'a str>,
+    /// Whether to use ANSI colors. Defaults to whether stdout is a terminal.
+    pub colors: bool,
+    /// Number of lines shown before and after the line of the error. Defaults to 0.
+    pub context_lines: usize,
+}
+
+impl Default for RenderOptions<'_> {
+    fn default() -> Self {
+        RenderOptions {
+            file_name: None,
+            colors: std::io::IsTerminal::is_terminal(&std::io::stdout()),
+            context_lines: 0,
         }
     }
 }
@@ -26,8 +95,13 @@ impl std::fmt::Display for ErrorKind {
 pub struct Error {
     kind: ErrorKind,
     remaining_bytes: usize,
+    /// Bytes the innermost rule that failed after matching something had matched, where the failing region starts.
+    span_bytes: usize,
     trace: Vec<String>,
     note: Option<String>,
+    /// Input the error was found in, for miette to show.
+    #[cfg(feature = "miette")]
+    pub(crate) source: Option<Box<crate::diagnostic::AttachedSource>>,
 }
 
 impl Error {
@@ -35,8 +109,52 @@ impl Error {
         Error {
             kind,
             remaining_bytes: input.len(),
+            span_bytes: 0,
             trace: vec![root.into()],
             note: None,
+            #[cfg(feature = "miette")]
+            source: None,
+        }
+    }
+
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    /// Expressions that failed, from the innermost one.
+    pub fn trace(&self) -> &[String] {
+        &self.trace
+    }
+
+    pub fn note(&self) -> Option<&str> {
+        self.note.as_deref()
+    }
+
+    /// Byte offset of the error in the input it was found in.
+    /// Expressions like `ANY` match single bytes, so errors can be found inside a character, in which case this is where the character starts.
+    pub fn offset(&self, input: &str) -> usize {
+        if self.remaining_bytes > input.len() {
+            panic!("Error::offset: the error doesn't come from this input");
+        }
+        char_start(input, input.len() - self.remaining_bytes)
+    }
+
+    /// Byte range of the failing region.
+    /// It goes from the start of the innermost rule that failed after matching something, to the character at the error, if any.
+    pub fn span(&self, input: &str) -> std::ops::Range<usize> {
+        let offset = self.offset(input);
+        let start = char_start(input, (input.len() - self.remaining_bytes).saturating_sub(self.span_bytes));
+        let char_len = input[offset..].chars().next().map(char::len_utf8).unwrap_or(0);
+        start..offset + char_len
+    }
+
+    pub fn line_col(&self, input: &str) -> LineCol {
+        let position = self.offset(input);
+        let line_start = input[..position].rfind('\n').map(|i| i + 1).unwrap_or(0);
+        LineCol {
+            line: input[..position].matches('\n').count() + 1,
+            col_utf8: position - line_start + 1,
+            col_utf16: input[line_start..position].encode_utf16().count() + 1,
         }
     }
 
@@ -52,34 +170,807 @@ impl Error {
         self
     }
 
+    /// Moves the error position back to the input itself, for errors found in an input followed by `padding` extra bytes.
+    pub fn without_padding(mut self, padding: usize) -> Self {
+        self.remaining_bytes -= padding;
+        self
+    }
+
+    /// Writes the error with the line it was found in, like rustc does.
+    pub fn render(&self, input: &str, out: &mut impl std::fmt::Write, options: RenderOptions) -> std::fmt::Result {
+        let [red, normal, blue, bold] = match options.colors {
+            true => [RED, NORMAL, BLUE, BOLD],
+            false => [""; 4],
+        };
+        let position = self.offset(input);
+        let LineCol { line: line_number, col_utf8, .. } = self.line_col(input);
+
+        let line_start = position + 1 - col_utf8;
+        let lines = input.split('\n').map(|line| line.strip_suffix('\r').unwrap_or(line)).collect::<Vec<_>>();
+        let line = lines[line_number - 1];
+        let first_line = line_number.saturating_sub(options.context_lines).max(1);
+        let last_line = (line_number + options.context_lines).min(lines.len());
+
+        // Tabs are kept under tabs so that the marker lines up with the text however tabs are displayed
+        let padding = input[line_start..position].chars().map(|c| if c == '\t' { '\t' } else { ' ' }).collect::<String>();
+        let span = self.span(input);
+        let marker_len = line.get(position - line_start..(span.end - line_start).min(line.len())).map(|s| s.chars().count()).unwrap_or(0).max(1);
+        let gutter = " ".repeat(last_line.to_string().len().max(2));
+
+        writeln!(out, "{red}error{normal}: {}", self.kind)?;
+        writeln!(out, "{blue}{gutter}-->{normal} {}:{}:{}", options.file_name.unwrap_or("input"), line_number, col_utf8)?;
+        writeln!(out, "{blue}{gutter} |{normal}")?;
+        for (n, line) in lines.iter().enumerate().take(last_line).skip(first_line - 1).map(|(i, line)| (i + 1, line)) {
+            writeln!(out, "{blue}{n:<width$}|{normal} {line}", width = gutter.len() + 1)?;
+            if n == line_number {
+                writeln!(out, "{blue}{gutter} |{normal} {padding}{red}{}{normal}", "^".repeat(marker_len))?;
+            }
+        }
+        if let Some(note) = &self.note {
+            writeln!(out, "{gutter} {blue}= {normal}{bold}note{normal}: {note}")?;
+        }
+        if !self.trace.is_empty() {
+            writeln!(out, "{gutter} {blue}= {normal}{bold}trace{normal}: {}", self.trace.join(", "))?;
+        }
+        Ok(())
+    }
+
+    /// Prints the error to stdout, with colors if it is a terminal.
     pub fn print(&self, input: &str) {
-        if self.remaining_bytes > input.len() {
-            panic!("Error::print: remaining_bytes is greater than input length");
+        let mut rendered = String::new();
+        self.render(input, &mut rendered, RenderOptions::default()).expect("writing to a String can't fail");
+        print!("{rendered}");
+    }
+
+    /// Converts the error to a pest error, whose positives and negatives are the rules that were expected and unexpected.
+    /// Like in pest, literals and characters aren't listed. Errors that involve no rule become custom errors with the same message.
+    pub fn into_pest<Rule: FromRuleName>(self, input: &str) -> pest::error::Error<Rule> {
+        let kinds = match &self.kind {
+            ErrorKind::ExpectedOneOf(kinds) => kinds.as_slice(),
+            kind => std::slice::from_ref(kind),
+        };
+        let positives = kinds.iter().filter_map(|kind| match kind {
+            ErrorKind::ExpectedRule(rule) => Rule::from_rule_name(rule),
+            _ => None,
+        }).collect::<Vec<_>>();
+        let negatives = kinds.iter().filter_map(|kind| match kind {
+            ErrorKind::NegPredFailed(rule) => Rule::from_rule_name(rule),
+            _ => None,
+        }).collect::<Vec<_>>();
+        let variant = match positives.is_empty() && negatives.is_empty() {
+            true => pest::error::ErrorVariant::CustomError { message: self.to_string() },
+            false => pest::error::ErrorVariant::ParsingError { positives, negatives },
+        };
+
+        // The span starts at the error, so that pest reports its line and column
+        let (start, end) = (self.offset(input), self.span(input).end);
+        match start == end {
+            true => pest::error::Error::new_from_pos(variant, pest::Position::new(input, start).expect("Error::into_pest: invalid position")),
+            false => pest::error::Error::new_from_span(variant, pest::Span::new(input, start, end).expect("Error::into_pest: invalid span")),
         }
-        let position = input.len() - self.remaining_bytes;
+    }
+}
 
-        let line_start = input[..position].rfind('\n').map(|i| i + 1).unwrap_or(0);
-        let line_end = input[position..].find('\n').map(|i| i + position).unwrap_or(input.len());
-        let line_number = input[..position].matches('\n').count() + 1;
-        let position_in_utf8_line = input[line_start..position].chars().count();
-
-        println!("{RED}error{NORMAL}: {}", self.kind);
-        println!("{BLUE}  -->{NORMAL} {}:{}:{}", self.trace[0], line_number, position - line_start + 1);
-        println!("{BLUE}   |{NORMAL}");
-        println!("{BLUE}{:<3}|{NORMAL} {}", line_number, &input[line_start..line_end]);
-        println!("{BLUE}   |{NORMAL} {}{RED}^{NORMAL}", " ".repeat(position_in_utf8_line));
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.kind)?;
         if let Some(note) = &self.note {
-            println!("   {BLUE}= {NORMAL}{BOLD}note{NORMAL}: {note}");
+            write!(f, " ({note})")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Fails on inputs too long for [`IdentEntry`](crate::IdentEntry) positions, before parsing them.
+/// `padding` is how many bytes the parser adds after the input, and `root` names the rule about to be parsed.
+/// This is used by the generated parser.
+pub fn check_input_len(input: &str, padding: usize, root: impl std::fmt::Display) -> Result<(), Error> {
+    let max_len = u32::MAX as usize - padding;
+    if input.len() <= max_len {
+        return Ok(());
+    }
+    Err(Error {
+        kind: ErrorKind::Expected("at most 4 GiB of input"),
+        remaining_bytes: input.len() - max_len,
+        span_bytes: 0,
+        trace: vec![root.to_string()],
+        note: Some(String::from("Positions in parsed idents are stored on 32 bits")),
+        #[cfg(feature = "miette")]
+        source: None,
+    })
+}
+
+/// Reports the failure of literals that were fused into one, at the start of the first of them that doesn't match, like it would on its own.
+/// `sentinel_len` is how many bytes the parser added after the input.
+/// This is used by the generated parser.
+pub fn fused_literals_error<E: ErrorSink>(input: &[u8], sentinel_len: usize, literals: &[&'static str], root: &'static str) -> E::Error {
+    let matched = input[..input.len() - sentinel_len].iter().zip(literals.iter().flat_map(|literal| literal.bytes())).take_while(|(c, expected)| **c == *expected).count();
+    let (mut start, mut i) = (0, 0);
+    while i + 1 < literals.len() && start + literals[i].len() <= matched {
+        start += literals[i].len();
+        i += 1;
+    }
+    E::new(ErrorKind::ExpectedValue(literals[i]), &input[start..], root)
+}
+
+/// Moves a byte offset back to the start of the character it is in.
+fn char_start(input: &str, mut offset: usize) -> usize {
+    while !input.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+/// Rules of a generated parser, found back from the names errors refer to them with.
+pub trait FromRuleName: pest::RuleType {
+    fn from_rule_name(name: &str) -> Option<Self>;
+}
+
+/// How generated parsing functions report failures.
+///
+/// Parsing functions are generic over it, so that each of them compiles to versions that record failures in detail,
+/// only keep the furthest one, or only tell where it is.
+/// Parsers first run the latter, and when it fails, only parse again the rule around the furthest failure.
+pub trait ErrorSink {
+    type Error;
+    /// Errors of the alternatives of a choice, gathered until they all failed.
+    type Alternatives: Default;
+    /// What was known about failures when a rule started, given back to [`ErrorSink::in_rule`].
+    type Mark: Copy;
+    /// Sink for failures that aren't errors, like the one ending a repetition.
+    /// Detailed errors still record them, so that a failure that follows can point to the deeper cause.
+    /// Offset errors keep reporting them too, so that rules in repetitions can recover.
+    type Discarded: ErrorSink;
+
+    fn new(kind: ErrorKind, input: &[u8], root: &'static str) -> Self::Error;
+    fn with_trace(error: Self::Error, trace: &'static str) -> Self::Error;
+    fn mark() -> Self::Mark;
+    /// Reports a failure of the rule that started at `input`.
+    /// Like pest does, rules that fail without matching anything are expected instead of what they contain,
+    /// unless what they contain is a single rule, which is more specific.
+    fn in_rule(error: Self::Error, input: &[u8], rule: &'static str, mark: Self::Mark) -> Self::Error;
+    /// Reports that the rule that started at `input` matched.
+    fn rule_matched(input: &[u8], rule: &'static str, mark: Self::Mark);
+    fn push_alternative(alternatives: &mut Self::Alternatives, error: Self::Error);
+    fn all(alternatives: Self::Alternatives, input: &[u8], root: &'static str) -> Self::Error;
+    /// Tells whether rules declared with `recover` skip what they fail on, instead of failing.
+    fn recovers() -> bool;
+    /// Records the failure of a rule that recovered, and was replaced by the `ERROR` ident at `node_idx`.
+    fn recovered(error: Self::Error, input: &[u8], rule: &'static str, mark: Self::Mark, node_idx: usize);
+}
+
+/// Everything that was expected at the furthest position a detailed parse failed at.
+/// Like in pest, this includes failures of alternatives that were discarded because a later one succeeded.
+struct Furthest {
+    remaining_bytes: usize,
+    expected: Vec<ErrorKind>,
+    /// Bytes the innermost rule that failed after matching something had matched, where the failing region starts.
+    span_bytes: usize,
+    /// Root of the first failure recorded there.
+    root: &'static str,
+    /// Frames of the traces of the failures there, each with the index of the frame before it.
+    frames: Vec<(u32, &'static str)>,
+    /// Changes whenever the frames are cleared, so that older failures don't use them.
+    generation: u32,
+}
+
+/// Index of the frame before the first one of a trace.
+const NO_FRAME: u32 = u32::MAX;
+
+impl Furthest {
+    const fn new() -> Furthest {
+        Furthest {
+            remaining_bytes: usize::MAX,
+            expected: Vec::new(),
+            span_bytes: 0,
+            root: "",
+            frames: Vec::new(),
+            generation: 0,
+        }
+    }
+
+    /// Starts over, keeping the allocations.
+    fn reset(&mut self, remaining_bytes: usize, root: &'static str) {
+        self.remaining_bytes = remaining_bytes;
+        self.expected.clear();
+        self.span_bytes = 0;
+        self.root = root;
+        self.frames.clear();
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    fn record(&mut self, kind: &ErrorKind, remaining_bytes: usize, root: &'static str) {
+        if remaining_bytes > self.remaining_bytes {
+            return;
+        }
+        if remaining_bytes < self.remaining_bytes {
+            self.reset(remaining_bytes, root);
+        }
+        for kind in match kind {
+            ErrorKind::ExpectedOneOf(kinds) => kinds.as_slice(),
+            kind => std::slice::from_ref(kind),
+        } {
+            if !self.expected.contains(kind) {
+                self.expected.push(kind.to_owned());
+            }
+        }
+    }
+
+    /// Adds a frame to the trace of a failure, which is only kept for failures at the furthest position.
+    fn push_frame(&mut self, failure: &mut DetailedFailure, frame: &'static str) {
+        if failure.remaining_bytes == self.remaining_bytes && failure.generation == self.generation {
+            self.frames.push((failure.frame, frame));
+            failure.frame = self.frames.len() as u32 - 1;
+        }
+    }
+
+    /// Builds the error of a failure, about everything that was expected at the furthest position.
+    fn error(&self, failure: DetailedFailure) -> Error {
+        let kind = match self.expected.as_slice() {
+            [kind] => kind.to_owned(),
+            expected => ErrorKind::ExpectedOneOf(expected.to_vec()),
+        };
+        let mut error = Error {
+            kind,
+            remaining_bytes: self.remaining_bytes,
+            span_bytes: self.span_bytes,
+            trace: vec![self.root.into()],
+            note: None,
+            #[cfg(feature = "miette")]
+            source: None,
+        };
+        if failure.remaining_bytes == self.remaining_bytes && failure.generation == self.generation {
+            let mut trace = Vec::new();
+            let mut frame = failure.frame;
+            while let Some((previous, name)) = self.frames.get(frame as usize) {
+                trace.push(String::from(*name));
+                frame = *previous;
+            }
+            trace.reverse();
+            error.trace = trace;
+            error.span_bytes = error.span_bytes.max(failure.span_bytes);
+        } else if failure.remaining_bytes < self.remaining_bytes {
+            // The failure comes from before a rule recovered and started over, and the rest of the input failed earlier
+            error.kind = ErrorKind::ExpectedOneOf(Vec::new());
+            error.remaining_bytes = failure.remaining_bytes;
+            error.span_bytes = failure.span_bytes;
+            error.trace = Vec::new();
+        }
+        error
+    }
+}
+
+thread_local! {
+    static FURTHEST: RefCell<Furthest> = const { RefCell::new(Furthest::new()) };
+}
+
+/// A failure of a detailed parse.
+/// Parsing functions pass it around instead of an [`Error`], which is only built for the failure that ends the parse.
+#[derive(Debug, Clone, Copy)]
+pub struct DetailedFailure {
+    remaining_bytes: usize,
+    /// Bytes the innermost rule that failed after matching something had matched.
+    span_bytes: usize,
+    /// Last frame of the trace, if the failure is at the furthest position.
+    frame: u32,
+    generation: u32,
+}
+
+/// Records everything that was expected at the furthest failure, with the trace of the failures there.
+/// Parses with it must be run through [`DetailedErrors::run`], which builds the error.
+pub struct DetailedErrors;
+
+impl DetailedErrors {
+    /// Runs a parse, and makes its error about everything that was expected at the furthest position any expression failed at.
+    pub fn run<T>(parse: impl FnOnce() -> Result<T, DetailedFailure>) -> Result<T, Error> {
+        FURTHEST.with(|furthest| furthest.borrow_mut().reset(usize::MAX, ""));
+        let result = parse();
+        result.map_err(|failure| FURTHEST.with(|furthest| furthest.borrow().error(failure)))
+    }
+}
+
+impl ErrorSink for DetailedErrors {
+    type Error = DetailedFailure;
+    /// The furthest failure of the alternatives, with the largest span of those that failed there.
+    type Alternatives = Option<DetailedFailure>;
+    /// Furthest position and number of expectations there when the rule started.
+    type Mark = (usize, usize);
+    type Discarded = DetailedErrors;
+
+    /// Detailed errors are only built when parsing again the rule around the furthest failure of an invalid input.
+    #[cold]
+    fn new(kind: ErrorKind, input: &[u8], root: &'static str) -> DetailedFailure {
+        FURTHEST.with(|furthest| {
+            let mut furthest = furthest.borrow_mut();
+            furthest.record(&kind, input.len(), root);
+            let mut failure = DetailedFailure {
+                remaining_bytes: input.len(),
+                span_bytes: 0,
+                frame: NO_FRAME,
+                generation: furthest.generation,
+            };
+            furthest.push_frame(&mut failure, root);
+            failure
+        })
+    }
+
+    #[inline]
+    fn with_trace(mut failure: DetailedFailure, trace: &'static str) -> DetailedFailure {
+        FURTHEST.with(|furthest| furthest.borrow_mut().push_frame(&mut failure, trace));
+        failure
+    }
+
+    #[inline]
+    fn mark() -> (usize, usize) {
+        FURTHEST.with(|furthest| {
+            let furthest = furthest.borrow();
+            (furthest.remaining_bytes, furthest.expected.len())
+        })
+    }
+
+    #[inline]
+    fn in_rule(mut failure: DetailedFailure, input: &[u8], rule: &'static str, (remaining_bytes, expected_len): (usize, usize)) -> DetailedFailure {
+        FURTHEST.with(|furthest| {
+            let mut furthest = furthest.borrow_mut();
+            if furthest.remaining_bytes == input.len() {
+                // What the rule expected there is replaced by the rule itself
+                let kept = if remaining_bytes == input.len() { expected_len } else { 0 };
+                let single_rule = furthest.expected.len() == kept + 1 && matches!(furthest.expected[kept], ErrorKind::ExpectedRule(_) | ErrorKind::NegPredFailed(_));
+                if !single_rule {
+                    furthest.expected.truncate(kept);
+                    furthest.record(&ErrorKind::ExpectedRule(rule), input.len(), rule);
+                }
+            } else if furthest.remaining_bytes < remaining_bytes && furthest.span_bytes == 0 {
+                // The furthest failure happened within this rule
+                furthest.span_bytes = input.len() - furthest.remaining_bytes;
+            }
+        });
+        if failure.remaining_bytes != input.len() && failure.span_bytes == 0 {
+            failure.span_bytes = input.len() - failure.remaining_bytes;
+        }
+        failure
+    }
+
+    #[inline(always)]
+    fn rule_matched(_input: &[u8], _rule: &'static str, _mark: (usize, usize)) {}
+
+    #[inline]
+    fn push_alternative(alternatives: &mut Option<DetailedFailure>, failure: DetailedFailure) {
+        match alternatives {
+            Some(furthest) if furthest.remaining_bytes < failure.remaining_bytes => (),
+            Some(furthest) if furthest.remaining_bytes == failure.remaining_bytes => furthest.span_bytes = furthest.span_bytes.max(failure.span_bytes),
+            _ => *alternatives = Some(failure),
+        }
+    }
+
+    #[inline]
+    fn all(alternatives: Option<DetailedFailure>, input: &[u8], root: &'static str) -> DetailedFailure {
+        match alternatives {
+            Some(failure) => DetailedErrors::with_trace(failure, root),
+            None => DetailedErrors::new(ErrorKind::ExpectedOneOf(Vec::new()), input, root),
+        }
+    }
+
+    #[inline]
+    fn recovers() -> bool {
+        is_recovering()
+    }
+
+    /// Records what was expected at the furthest position the rule reached, and starts over for the rest of the input.
+    #[cold]
+    fn recovered(failure: DetailedFailure, input: &[u8], rule: &'static str, mark: (usize, usize), node_idx: usize) {
+        let failure = DetailedErrors::in_rule(failure, input, rule, mark);
+        let error = FURTHEST.with(|furthest| {
+            let mut furthest = furthest.borrow_mut();
+            // The rule may have failed further in an alternative that was tried before it recovered
+            let error = match furthest.remaining_bytes < input.len() {
+                true => furthest.error(failure),
+                false => Error {
+                    kind: ErrorKind::ExpectedRule(rule),
+                    remaining_bytes: input.len(),
+                    span_bytes: 0,
+                    trace: vec![rule.into()],
+                    note: None,
+                    #[cfg(feature = "miette")]
+                    source: None,
+                },
+            };
+            furthest.reset(usize::MAX, "");
+            error
+        });
+        record_recovered(error, input, node_idx);
+    }
+}
+
+/// What the quick pass of a parser knows about its furthest failure.
+/// It has no destructor, so that accessing it doesn't need to check whether the thread registered one.
+struct Quick {
+    remaining_bytes: Cell<usize>,
+    /// Length of the input where the failure being reported happened.
+    /// Failures are reported one at a time, so that parsing functions don't need to pass it around.
+    failure_bytes: Cell<usize>,
+    /// Whether a rule that failed after matching something contains the furthest failure, which gives detailed errors their span.
+    span_rule: Cell<bool>,
+    /// Length of the input where the innermost rule that contains every failure at the furthest position started,
+    /// or [`NO_RULE`] when there is none yet.
+    resume_bytes: Cell<usize>,
+    resume_rule: Cell<&'static str>,
+    /// Length of the trace of the failure that left that rule, since it left.
+    trace_len: Cell<usize>,
+}
+
+const NO_RULE: usize = usize::MAX;
+
+impl Quick {
+    /// Forgets the rule that contained every failure at the furthest position, after one happened outside of it.
+    #[inline(always)]
+    fn forget_rule(&self) {
+        self.resume_bytes.set(NO_RULE);
+    }
+
+    /// Failures are frequent in valid inputs too, since every failed alternative reports one, but few get further than all the others.
+    #[cold]
+    fn move_furthest(&self, remaining_bytes: usize) {
+        self.remaining_bytes.set(remaining_bytes);
+        self.span_rule.set(false);
+        self.forget_rule();
+    }
+
+    #[inline(always)]
+    fn resume_from(&self, rule: &'static str, input: &[u8]) {
+        self.resume_bytes.set(input.len());
+        self.resume_rule.set(rule);
+        self.trace_len.set(0);
+    }
+
+    /// The rule to parse again from, with the length of the input where it started.
+    fn resume(&self) -> Option<(&'static str, usize)> {
+        match self.resume_bytes.replace(NO_RULE) {
+            NO_RULE => None,
+            bytes => Some((self.resume_rule.get(), bytes)),
+        }
+    }
+
+    /// The first rule that is left after all failures at the furthest position happened within it is the innermost one to contain them.
+    #[inline(always)]
+    fn left_rule(&self, input: &[u8], rule: &'static str, mark: usize) {
+        if self.remaining_bytes.get() < mark && self.resume_bytes.get() == NO_RULE {
+            self.resume_from(rule, input);
+        }
+    }
+}
+
+/// Frames are written over those of previous traces, so that starting a trace doesn't need to access them.
+#[cold]
+fn push_quick_trace(quick: &Quick, trace: &'static str) {
+    QUICK_TRACE.with(|frames| {
+        let mut frames = frames.borrow_mut();
+        frames.truncate(quick.trace_len.get());
+        frames.push(trace);
+    });
+    quick.trace_len.set(quick.trace_len.get() + 1);
+}
+
+thread_local! {
+    static QUICK: Quick = const {
+        Quick {
+            remaining_bytes: Cell::new(usize::MAX),
+            failure_bytes: Cell::new(usize::MAX),
+            span_rule: Cell::new(false),
+            resume_bytes: Cell::new(NO_RULE),
+            resume_rule: Cell::new(""),
+            trace_len: Cell::new(0),
+        }
+    };
+    /// Frames of the trace of the failure that left the rule to resume from.
+    static QUICK_TRACE: RefCell<Vec<&'static str>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Only records where the furthest failure is, which is cheap enough for the pass that valid inputs go through.
+/// When it fails, [`QuickErrors::run`] describes the failure with [`DetailedErrors`], or [`QuickErrors::run_offset`] with [`OffsetErrors`],
+/// by parsing again the innermost rule that contains every failure at the furthest position, instead of the whole input.
+pub struct QuickErrors;
+
+impl QuickErrors {
+    /// Runs a parse of `input`, that starts with the rule named `root`.
+    /// When it fails, `detailed` parses again with detailed errors, given the name of a rule and the input where it started,
+    /// so that the error is the same as with [`DetailedErrors::run`].
+    pub fn run<'i, T>(
+        input: &'i [u8],
+        root: &'static str,
+        parse: impl FnOnce() -> Result<T, ()>,
+        detailed: impl FnOnce(&'static str, &'i [u8]) -> Result<&'i [u8], DetailedFailure>,
+    ) -> Result<T, Error> {
+        QUICK.with(|quick| {
+            quick.remaining_bytes.set(usize::MAX);
+            quick.span_rule.set(false);
+            quick.forget_rule();
+        });
+        if let Ok(value) = parse() {
+            return Ok(value);
+        }
+        let (remaining_bytes, furthest, resume, trace_len) = QUICK.with(|quick| {
+            (quick.failure_bytes.get(), quick.remaining_bytes.get(), quick.resume(), quick.trace_len.get())
+        });
+        let (rule, start) = resume.unwrap_or((root, input.len()));
+        let mut trace = QUICK_TRACE.with(|trace| trace.take());
+        trace.truncate(if resume.is_some() { trace_len } else { 0 });
+
+        FURTHEST.with(|furthest| furthest.borrow_mut().reset(usize::MAX, ""));
+        let failure = match parsing_again(|| detailed(rule, &input[input.len() - start..])) {
+            // The failure that ended the parse came from the rule, and got the rest of its trace after it
+            Err(failure) if remaining_bytes == furthest => failure,
+            // It wasn't at the furthest position, so the trace only has the root of the first failure there
+            _ => DetailedFailure { remaining_bytes: usize::MAX, span_bytes: 0, frame: NO_FRAME, generation: 0 },
+        };
+        let mut error = FURTHEST.with(|furthest| furthest.borrow().error(failure));
+        if failure.remaining_bytes == furthest {
+            error.trace.extend(trace.into_iter().map(String::from));
+        }
+        Err(error)
+    }
+
+    /// Like [`QuickErrors::run`], with `offset` parsing again with offset errors,
+    /// so that the error is the same as with [`OffsetErrors::run`].
+    pub fn run_offset<'i, T>(
+        input: &'i [u8],
+        root: &'static str,
+        parse: impl FnOnce() -> Result<T, ()>,
+        offset: impl FnOnce(&'static str, &'i [u8]) -> Result<&'i [u8], ()>,
+    ) -> Result<T, Error> {
+        QUICK.with(|quick| {
+            quick.remaining_bytes.set(usize::MAX);
+            quick.span_rule.set(false);
+            quick.forget_rule();
+        });
+        if let Ok(value) = parse() {
+            return Ok(value);
+        }
+        let (rule, start) = QUICK.with(|quick| quick.resume()).unwrap_or((root, input.len()));
+        FURTHEST_FAILURE.with(|furthest| furthest.replace(Failure::new()));
+        let _ = parsing_again(|| offset(rule, &input[input.len() - start..]));
+        Err(FURTHEST_FAILURE.with(|furthest| furthest.replace(Failure::new())).into_error())
+    }
+}
+
+impl ErrorSink for QuickErrors {
+    type Error = ();
+    /// Length of the input where the furthest alternative failed.
+    type Alternatives = Option<usize>;
+    /// Furthest position when the rule started.
+    type Mark = usize;
+    type Discarded = QuickErrors;
+
+    #[inline]
+    fn new(_kind: ErrorKind, input: &[u8], _root: &'static str) {
+        QUICK.with(|quick| {
+            quick.failure_bytes.set(input.len());
+            let furthest = quick.remaining_bytes.get();
+            if input.len() < furthest {
+                quick.move_furthest(input.len());
+            } else if input.len() == furthest {
+                quick.forget_rule();
+            }
+        });
+    }
+
+    #[inline]
+    fn with_trace(_error: (), trace: &'static str) {
+        QUICK.with(|quick| {
+            if quick.failure_bytes.get() == quick.remaining_bytes.get() && quick.resume_bytes.get() != NO_RULE {
+                push_quick_trace(quick, trace);
+            }
+        });
+    }
+
+    #[inline]
+    fn mark() -> usize {
+        QUICK.with(|quick| quick.remaining_bytes.get())
+    }
+
+    #[inline]
+    fn in_rule(_error: (), input: &[u8], rule: &'static str, mark: usize) {
+        QUICK.with(|quick| {
+            let furthest = quick.remaining_bytes.get();
+            if furthest == input.len() {
+                // Detailed errors replace what the rule expected there by the rule itself
+                quick.forget_rule();
+            } else if furthest < mark && !quick.span_rule.get() {
+                // Detailed errors get their span from this rule
+                quick.span_rule.set(true);
+                quick.resume_from(rule, input);
+            }
+            quick.left_rule(input, rule, mark);
+        });
+    }
+
+    #[inline]
+    fn rule_matched(input: &[u8], rule: &'static str, mark: usize) {
+        QUICK.with(|quick| quick.left_rule(input, rule, mark));
+    }
+
+    #[inline]
+    fn push_alternative(alternatives: &mut Option<usize>, _error: ()) {
+        let failure = QUICK.with(|quick| quick.failure_bytes.get());
+        match alternatives {
+            Some(furthest) if *furthest <= failure => (),
+            _ => *alternatives = Some(failure),
+        }
+    }
+
+    #[inline]
+    fn all(alternatives: Option<usize>, input: &[u8], root: &'static str) {
+        match alternatives {
+            Some(failure) => {
+                QUICK.with(|quick| quick.failure_bytes.set(failure));
+                QuickErrors::with_trace((), root)
+            }
+            None => QuickErrors::new(ErrorKind::ExpectedOneOf(Vec::new()), input, root),
+        }
+    }
+
+    #[inline(always)]
+    fn recovers() -> bool {
+        false
+    }
+
+    #[inline(always)]
+    fn recovered(_error: (), _input: &[u8], _rule: &'static str, _mark: usize, _node_idx: usize) {}
+}
+
+/// The furthest failure of a parse, as recorded by [`OffsetErrors`].
+struct Failure {
+    kind: ErrorKind,
+    remaining_bytes: usize,
+}
+
+impl Failure {
+    const fn new() -> Failure {
+        Failure {
+            kind: ErrorKind::ExpectedOneOf(Vec::new()),
+            remaining_bytes: usize::MAX,
+        }
+    }
+
+    /// Only failures further than all the previous ones replace them, which is rare.
+    #[cold]
+    fn replace(&mut self, kind: ErrorKind, remaining_bytes: usize) {
+        *self = Failure { kind, remaining_bytes };
+    }
+
+    fn into_error(self) -> Error {
+        Error {
+            kind: self.kind,
+            remaining_bytes: self.remaining_bytes,
+            span_bytes: 0,
+            trace: Vec::new(),
+            note: None,
+            #[cfg(feature = "miette")]
+            source: None,
         }
-        println!("   {BLUE}= {NORMAL}{BOLD}trace{NORMAL}: {}", self.trace.join(", "));
     }
+}
+
+thread_local! {
+    static FURTHEST_FAILURE: RefCell<Failure> = const { RefCell::new(Failure::new()) };
+}
 
-    pub fn into_pest<Rule: pest::RuleType>(self, input: &str) -> pest::error::Error<Rule> {
-        pest::error::Error::new_from_pos(
-            pest::error::ErrorVariant::CustomError {
-                message: format!("{}", self.kind),
-            },
-            pest::Position::new(input, input.len() - self.remaining_bytes).expect("Error::into_pest: invalid position"),
-        )
+/// Only keeps the furthest failure, without any trace.
+/// Like with detailed errors, this includes failures that were discarded, like the one ending a repetition.
+/// This is used by parsers with `#[faster_pest(errors = "offset")]`, and never allocates.
+pub struct OffsetErrors;
+
+impl OffsetErrors {
+    /// Runs a parse, and turns the furthest failure it recorded into an [`Error`].
+    pub fn run<T>(parse: impl FnOnce() -> Result<T, ()>) -> Result<T, Error> {
+        FURTHEST_FAILURE.with(|furthest| furthest.replace(Failure::new()));
+        let result = parse();
+        let furthest = FURTHEST_FAILURE.with(|furthest| furthest.replace(Failure::new()));
+        result.map_err(|()| furthest.into_error())
     }
 }
+
+impl ErrorSink for OffsetErrors {
+    type Error = ();
+    type Alternatives = ();
+    /// Furthest position when the rule started.
+    type Mark = usize;
+    type Discarded = OffsetErrors;
+
+    #[inline]
+    fn new(kind: ErrorKind, input: &[u8], _root: &'static str) {
+        FURTHEST_FAILURE.with(|furthest| {
+            let mut furthest = furthest.borrow_mut();
+            if input.len() < furthest.remaining_bytes {
+                furthest.replace(kind, input.len());
+            }
+        });
+    }
+
+    #[inline(always)]
+    fn with_trace(_error: (), _trace: &'static str) {}
+
+    #[inline]
+    fn mark() -> usize {
+        FURTHEST_FAILURE.with(|furthest| furthest.borrow().remaining_bytes)
+    }
+
+    #[inline]
+    fn in_rule(_error: (), input: &[u8], rule: &'static str, remaining_bytes: usize) {
+        FURTHEST_FAILURE.with(|furthest| {
+            let mut furthest = furthest.borrow_mut();
+            let failed_here = furthest.remaining_bytes == input.len() && remaining_bytes > input.len();
+            if failed_here && !matches!(furthest.kind, ErrorKind::ExpectedRule(_) | ErrorKind::NegPredFailed(_)) {
+                furthest.kind = ErrorKind::ExpectedRule(rule);
+            }
+        });
+    }
+
+    #[inline(always)]
+    fn rule_matched(_input: &[u8], _rule: &'static str, _mark: usize) {}
+
+    #[inline(always)]
+    fn push_alternative(_alternatives: &mut (), _error: ()) {}
+
+    #[inline(always)]
+    fn all(_alternatives: (), _input: &[u8], _root: &'static str) {}
+
+    #[inline]
+    fn recovers() -> bool {
+        is_recovering()
+    }
+
+    /// Records the furthest failure of the rule, and starts over for the rest of the input.
+    #[cold]
+    fn recovered(_error: (), input: &[u8], rule: &'static str, mark: usize, node_idx: usize) {
+        OffsetErrors::in_rule((), input, rule, mark);
+        let furthest = FURTHEST_FAILURE.with(|furthest| furthest.replace(Failure::new()));
+        // The rule may have failed further in an alternative that was tried before it recovered
+        let failure = match furthest.remaining_bytes < input.len() {
+            true => furthest,
+            false => Failure { kind: ErrorKind::ExpectedRule(rule), remaining_bytes: input.len() },
+        };
+        record_recovered(failure.into_error(), input, node_idx);
+    }
+}
+
+/// Discards failures, so that parsing functions compile to simple checks.
+pub struct NoErrors;
+
+impl ErrorSink for NoErrors {
+    type Error = ();
+    type Alternatives = ();
+    type Mark = ();
+    type Discarded = NoErrors;
+
+    #[inline(always)]
+    fn new(_kind: ErrorKind, _input: &[u8], _root: &'static str) {}
+
+    #[inline(always)]
+    fn with_trace(_error: (), _trace: &'static str) {}
+
+    #[inline(always)]
+    fn mark() {}
+
+    #[inline(always)]
+    fn in_rule(_error: (), _input: &[u8], _rule: &'static str, _mark: ()) {}
+
+    #[inline(always)]
+    fn rule_matched(_input: &[u8], _rule: &'static str, _mark: ()) {}
+
+    #[inline(always)]
+    fn push_alternative(_alternatives: &mut (), _error: ()) {}
+
+    #[inline(always)]
+    fn all(_alternatives: (), _input: &[u8], _root: &'static str) {}
+
+    #[inline(always)]
+    fn recovers() -> bool {
+        false
+    }
+
+    #[inline(always)]
+    fn recovered(_error: (), _input: &[u8], _rule: &'static str, _mark: (), _node_idx: usize) {}
+}
@@ -1,18 +1,163 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, cell::OnceCell};
 
 pub trait IdentTrait: Copy {
     type Rule: pest::RuleType;
 
     fn as_rule(&self) -> Self::Rule;
     fn as_str(&self) -> &str;
+
+    /// Builds the ident of the variant with the given index, matching `text`.
+    /// This is used to resolve [`IdentEntry`]s.
+    ///
+    /// # Safety
+    ///
+    /// `variant` must be the index of a variant, and `text` must outlive the returned ident.
+    unsafe fn from_entry(variant: u16, text: &str) -> Self;
+}
+
+/// A parsed ident, as stored by the parser.
+///
+/// Positions are counted from the end of the input, since parsing functions only see what remains of it.
+/// Inputs are thus limited to 4 GiB, which generated parsers check with [`check_input_len`](crate::check_input_len).
+#[derive(Debug, Clone, Copy)]
+pub struct IdentEntry {
+    /// Index of the variant of the ident.
+    variant: u16,
+    /// Length of the remaining input where the ident starts.
+    from_end: u32,
+    len: u32,
+    /// Index of the next ident that isn't a child of this one.
+    end_idx: u32,
+}
+
+impl IdentEntry {
+    #[inline(always)]
+    pub fn new(variant: u16, from_end: usize, len: usize, end_idx: usize) -> Self {
+        Self {
+            variant,
+            from_end: from_end as u32,
+            len: len as u32,
+            end_idx: end_idx as u32,
+        }
+    }
+
+    pub(crate) fn variant(&self) -> u16 {
+        self.variant
+    }
+
+    pub(crate) fn start_from_end(&self) -> usize {
+        self.from_end as usize
+    }
+
+    pub(crate) fn end_idx(&self) -> usize {
+        self.end_idx as usize
+    }
+
+    /// # Safety
+    ///
+    /// The entry must have been produced by parsing `input`.
+    pub(crate) unsafe fn as_str<'i>(&self, input: &'i str) -> &'i str {
+        let start = input.len() - self.from_end as usize;
+        input.get_unchecked(start..start + self.len as usize)
+    }
+
+    /// # Safety
+    ///
+    /// The entry must have been produced by parsing `input`.
+    pub(crate) unsafe fn resolve<I: IdentTrait>(&self, input: &str) -> I {
+        I::from_entry(self.variant, self.as_str(input))
+    }
+}
+
+/// Builds the idents of all entries, for APIs that hand out references to them.
+///
+/// # Safety
+///
+/// The entries must have been produced by parsing `input`.
+pub(crate) unsafe fn resolve_all<I: IdentTrait>(entries: &[IdentEntry], input: &str) -> Vec<I> {
+    entries.iter().map(|entry| entry.resolve(input)).collect()
+}
+
+/// Where generated parsing functions store idents.
+///
+/// Parsing functions are generic over it, so that validation compiles to a version that stores nothing.
+pub trait IdentSink {
+    /// Number of idents stored so far.
+    fn count(&self) -> usize;
+
+    /// Makes room for an ident whose children are about to be parsed, and returns its index.
+    fn open(&mut self) -> usize;
+
+    /// Stores an ident in the room made by [`IdentSink::open`].
+    ///
+    /// # Safety
+    ///
+    /// `idx` must have been returned by [`IdentSink::open`], and not been rolled back since.
+    unsafe fn close(&mut self, idx: usize, entry: IdentEntry);
+
+    /// Forgets the idents stored after the first `len` ones, when parsing fails.
+    fn rollback(&mut self, len: usize);
+}
+
+impl IdentSink for Vec<IdentEntry> {
+    #[inline(always)]
+    fn count(&self) -> usize {
+        self.len()
+    }
+
+    #[inline(always)]
+    fn open(&mut self) -> usize {
+        let idx = self.len();
+        if idx == self.capacity() {
+            self.reserve(1);
+        }
+        // The entry is left uninitialized until `close`, and `rollback` never reads it
+        unsafe { self.set_len(idx + 1) };
+        idx
+    }
+
+    #[inline(always)]
+    unsafe fn close(&mut self, idx: usize, entry: IdentEntry) {
+        *self.get_unchecked_mut(idx) = entry;
+    }
+
+    #[inline(always)]
+    fn rollback(&mut self, len: usize) {
+        self.truncate(len);
+    }
+}
+
+/// Stores nothing, for parsing that only validates the input.
+pub struct NoIdents;
+
+impl IdentSink for NoIdents {
+    #[inline(always)]
+    fn count(&self) -> usize {
+        0
+    }
+
+    #[inline(always)]
+    fn open(&mut self) -> usize {
+        0
+    }
+
+    #[inline(always)]
+    unsafe fn close(&mut self, _idx: usize, _entry: IdentEntry) {}
+
+    #[inline(always)]
+    fn rollback(&mut self, _len: usize) {}
 }
 
 #[derive(Clone)]
-pub struct IdentList<I: IdentTrait> {
-    all_idents: Vec<(I, usize)>,
+pub struct IdentList<'i, I: IdentTrait> {
+    input: &'i str,
+    all_idents: Vec<IdentEntry>,
+    /// Idents of `all_idents`, only built when [`IdentRef::ident`] is first called.
+    /// They take more room than the entries, so this more than doubles the memory the list uses.
+    idents: OnceCell<Vec<I>>,
 }
 
-impl<I: IdentTrait> IdentList<I> {
+impl<'i, I: IdentTrait> IdentList<'i, I> {
     /// This is used by the generated parser to convert its output to an IdentList.
     /// **You should not ever need to use this.**
     /// 
@@ -20,23 +165,30 @@ impl<I: IdentTrait> IdentList<I> {
     /// 
     /// The whole implementation assumes that the arguments of this function are valid.
     /// When this method is called by generated code, the input is guaranteed to be valid.
-    pub unsafe fn from_idents(idents: Vec<(I, usize)>) -> Self {
+    pub unsafe fn from_idents(idents: Vec<IdentEntry>, input: &'i str) -> Self {
         Self {
-            all_idents: idents
+            input,
+            all_idents: idents,
+            idents: OnceCell::new(),
         }
     }
 
+    fn idents(&self) -> &[I] {
+        // This is safe for the same reason as in `IdentRef::ident`.
+        self.idents.get_or_init(|| unsafe { resolve_all(&self.all_idents, self.input) })
+    }
+
     /// # Safety
     /// 
     /// The caller must ensure that `idx` is a valid index.
     pub unsafe fn get_unchecked(&self, idx: usize) -> IdentRef<'_, I> {
         IdentRef {
             ident_list: self,
-            range: idx..self.all_idents.get_unchecked(idx).1,
+            range: idx..self.all_idents.get_unchecked(idx).end_idx(),
         }
     }
 
-    pub fn root(&self) -> IdentRef<I> {
+    pub fn root(&self) -> IdentRef<'_, I> {
         IdentRef {
             ident_list: self,
             range: 0..self.all_idents.len(),
@@ -44,9 +196,60 @@ impl<I: IdentTrait> IdentList<I> {
     }
 }
 
-impl<'i, I: IdentTrait> IntoIterator for &'i IdentList<I> {
-    type Item = IdentRef<'i, I>;
-    type IntoIter = IdentIter<'i, I>;
+/// Estimates how many idents parsing `input` will produce, so that they are allocated at once.
+/// A wrong estimate is cheap: the buffer grows geometrically when it is too small.
+pub fn idents_capacity(input: &str) -> usize {
+    input.len() / 8 + 16
+}
+
+/// An allocation that is reused between parses, to avoid allocating idents for every document.
+///
+/// ```ignore
+/// let mut buffer = IdentBuffer::new();
+/// for document in documents {
+///     let idents = MyParser::parse_file_into(&mut buffer, &document)?;
+///     // ...
+///     buffer.recycle(idents);
+/// }
+/// ```
+#[derive(Default)]
+pub struct IdentBuffer {
+    idents: Vec<IdentEntry>,
+}
+
+impl IdentBuffer {
+    pub const fn new() -> Self {
+        Self {
+            idents: Vec::new(),
+        }
+    }
+
+    /// Takes the allocation as an empty vector with room for at least `capacity` idents.
+    /// This is used by the generated parser.
+    pub fn take(&mut self, capacity: usize) -> Vec<IdentEntry> {
+        let mut idents = std::mem::take(&mut self.idents);
+        idents.reserve(capacity);
+        idents
+    }
+
+    /// Keeps the allocation of a vector for the next parse.
+    /// This is used by the generated parser.
+    pub fn put_back(&mut self, mut idents: Vec<IdentEntry>) {
+        if idents.capacity() > self.idents.capacity() {
+            idents.clear();
+            self.idents = idents;
+        }
+    }
+
+    /// Gives the allocation of parsed idents back to the buffer, once they are no longer needed.
+    pub fn recycle<I: IdentTrait>(&mut self, idents: IdentList<'_, I>) {
+        self.put_back(idents.all_idents);
+    }
+}
+
+impl<'a, 'i, I: IdentTrait> IntoIterator for &'a IdentList<'i, I> {
+    type Item = IdentRef<'a, I>;
+    type IntoIter = IdentIter<'a, I>;
 
     fn into_iter(self) -> Self::IntoIter {
         IdentIter {
@@ -57,10 +260,10 @@ impl<'i, I: IdentTrait> IntoIterator for &'i IdentList<I> {
     }
 }
 
-impl<I: IdentTrait> std::fmt::Debug for IdentList<I> {
+impl<'i, I: IdentTrait> std::fmt::Debug for IdentList<'i, I> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_list()
-            .entries(self.all_idents.iter().map(|(ident, _)| ident.as_str()))
+            .entries(self.all_idents.iter().map(|entry| unsafe { entry.as_str(self.input) }))
             .finish()
     }
 }
@@ -68,17 +271,19 @@ impl<I: IdentTrait> std::fmt::Debug for IdentList<I> {
 
 #[derive(Clone)]
 pub struct IdentRef<'i, I: IdentTrait> {
-    ident_list: &'i IdentList<I>,
+    ident_list: &'i IdentList<'i, I>,
     range: std::ops::Range<usize>,
 }
 
 impl<'i, I: IdentTrait> IdentRef<'i, I> {
+    /// Returns the ident, which the list stores as a compact entry until then.
+    ///
+    /// The first call builds the idents of every entry of the list at once, so that references to them can be handed out.
+    /// This allocates more than the entries take themselves, so prefer [`IdentRef::as_rule`] and [`IdentRef::as_str`],
+    /// which resolve one entry at a time without allocating.
     pub fn ident(&self) -> &'i I {
-        // This is safe if the data is valid.
-        // The data is valid because it originally comes from `Pairs2::from_idents`, which is only called with valid data.
-        unsafe {
-            &self.ident_list.all_idents.get_unchecked(self.range.start).0
-        }
+        // This is safe because ranges always start at the index of an ident.
+        unsafe { self.ident_list.idents().get_unchecked(self.range.start) }
     }
 
     pub fn idx(&self) -> usize {
@@ -86,11 +291,19 @@ impl<'i, I: IdentTrait> IdentRef<'i, I> {
     }
 
     pub fn as_str(&self) -> &'i str {
-        self.ident().as_str()
+        // This is safe if the data is valid.
+        // The data is valid because it originally comes from `IdentList::from_idents`, which is only called with valid data.
+        unsafe {
+            self.ident_list.all_idents.get_unchecked(self.range.start).as_str(self.ident_list.input)
+        }
     }
 
     pub fn as_rule(&self) -> I::Rule {
-        self.ident().as_rule()
+        // This is safe for the same reason as in `as_str`.
+        // Resolving the entry alone avoids building all idents when only rules are needed.
+        unsafe {
+            self.ident_list.all_idents.get_unchecked(self.range.start).resolve::<I>(self.ident_list.input).as_rule()
+        }
     }
 
     pub fn children_count(&self) -> usize {
@@ -116,18 +329,18 @@ impl<'i, I: IdentTrait> IdentRef<'i, I> {
     }
 }
 
-impl<'i, I: IdentTrait> AsRef<str> for IdentRef<'i, I> {
-    fn as_ref(&self) -> &'i str {
-        self.ident().as_str()
-    }
-}
-
 impl<'i, I: IdentTrait> AsRef<I> for IdentRef<'i, I> {
     fn as_ref(&self) -> &'i I {
         self.ident()
     }
 }
 
+impl<'i, I: IdentTrait> AsRef<str> for IdentRef<'i, I> {
+    fn as_ref(&self) -> &'i str {
+        self.as_str()
+    }
+}
+
 impl<'i, I: IdentTrait> std::fmt::Debug for IdentRef<'i, I> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if self.range.len() > 1 {
@@ -146,7 +359,7 @@ impl<'i, I: IdentTrait> std::fmt::Debug for IdentRef<'i, I> {
 
 #[derive(Clone)]
 pub struct IdentIter<'i, I: IdentTrait> {
-    ident_list: &'i IdentList<I>,
+    ident_list: &'i IdentList<'i, I>,
     range: std::ops::Range<usize>,
     i: usize,
 }
@@ -201,8 +414,8 @@ impl<'i, I: IdentTrait> Iterator for IdentIter<'i, I> {
         let start = self.i + self.range.start;
         let end = unsafe {
             // This is safe if the data is valid.
-            // The data is valid because it originally comes from `Pairs2::from_idents`, which is only called with valid data.
-            self.ident_list.all_idents.get_unchecked(start).1
+            // The data is valid because it originally comes from `IdentList::from_idents`, which is only called with valid data.
+            self.ident_list.all_idents.get_unchecked(start).end_idx()
         };
         self.i = end - self.range.start;
 
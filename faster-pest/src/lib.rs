@@ -6,3 +6,13 @@ mod pairs;
 pub use pairs::*;
 mod ident;
 pub use ident::*;
+mod scan;
+pub use scan::*;
+mod sentinel;
+pub use sentinel::*;
+mod recovery;
+pub use recovery::*;
+mod profile;
+pub use profile::*;
+#[cfg(feature = "miette")]
+mod diagnostic;
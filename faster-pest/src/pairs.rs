@@ -1,4 +1,4 @@
-use std::rc::Rc;
+use std::{cell::OnceCell, rc::Rc};
 use crate::*;
 use pest::*;
 
@@ -9,32 +9,39 @@ pub struct Pair2<'i, I: IdentTrait> {
     /// The original input that was parsed.
     original_input: &'i str,
     /// A reference to the output of the parsing.
-    all_idents: Rc<Vec<(I, usize)>>,
+    all_idents: Rc<Vec<IdentEntry>>,
     /// The range indicates where the [`Pair2`] is stored in `all_idents`.
     /// `all_idents[range.start]` is the ident of the [`Pair2`], and `all_idents[range.start + 1..range.end]` are the children.
     range: std::ops::Range<usize>,
+    /// Idents of `all_idents`, only built when [`Pair2::ident`] is first called.
+    idents: Rc<OnceCell<Vec<I>>>,
 }
 
 impl<'i, I: IdentTrait> Pair2<'i, I> {
+    /// Returns the ident, building those of every entry at once on the first call, like [`IdentRef::ident`](crate::IdentRef::ident).
+    /// Prefer [`Pair2::as_rule`] and [`Pair2::as_str`] when they are enough, since they don't allocate.
     pub fn ident(&self) -> &I {
         // This is safe if the data is valid.
         // The data is valid because it originally comes from `Pairs2::from_idents`, which is only called with valid data.
         unsafe {
-            &self.all_idents.get_unchecked(self.range.start).0
+            let idents = self.idents.get_or_init(|| resolve_all(&self.all_idents, self.original_input));
+            idents.get_unchecked(self.range.start)
         }
     }
 
     pub fn as_rule(&self) -> I::Rule {
-        self.ident().as_rule()
+        // This is safe for the same reason as in `ident`.
+        // Resolving the entry alone avoids building all idents when only rules are needed.
+        unsafe {
+            self.all_idents.get_unchecked(self.range.start).resolve::<I>(self.original_input).as_rule()
+        }
     }
 
     pub fn as_str(&self) -> &'i str {
         // This is safe if the data is valid.
         // The data is valid because it originally comes from `Pairs2::from_idents`, which is only called with valid data.
         unsafe {
-            let str_start = self.ident().as_str().as_ptr() as usize - self.original_input.as_ptr() as usize;
-            let str_end = str_start + self.ident().as_str().len();    
-            self.original_input.get_unchecked(str_start..str_end)
+            self.all_idents.get_unchecked(self.range.start).as_str(self.original_input)
         }
     }
 
@@ -55,6 +62,7 @@ impl<'i, I: IdentTrait> Pair2<'i, I> {
             range: self.range.start + 1..self.range.end,
             initial_text: self.original_input,
             i: 0,
+            idents: Rc::clone(&self.idents),
         }
     }
 
@@ -64,6 +72,7 @@ impl<'i, I: IdentTrait> Pair2<'i, I> {
             range: self.range.start + 1..self.range.end,
             initial_text: self.original_input,
             i: 0,
+            idents: self.idents,
         }
     }
 }
@@ -86,10 +95,11 @@ impl<'i, I: IdentTrait> std::fmt::Debug for Pair2<'i, I> {
 /// To iterate over all [`Pair2`]s, use [`Pair2::into_inner`] on yielded [`Pair2`]s.
 #[derive(Clone)]
 pub struct Pairs2<'i, I: IdentTrait> {
-    all_idents: Rc<Vec<(I, usize)>>,
+    all_idents: Rc<Vec<IdentEntry>>,
     range: std::ops::Range<usize>,
     initial_text: &'i str,
     i: usize,
+    idents: Rc<OnceCell<Vec<I>>>,
 }
 
 impl<'i, I: IdentTrait> Pairs2<'i, I> {
@@ -100,12 +110,13 @@ impl<'i, I: IdentTrait> Pairs2<'i, I> {
     /// 
     /// The whole [Pairs2] and [Pair2] implementation assumes that the arguments of this function are valid.
     /// When this method is called by generated code, the input is guaranteed to be valid.
-    pub unsafe fn from_idents(idents: Vec<(I, usize)>, initial_text: &'i str) -> Self {
+    pub unsafe fn from_idents(idents: Vec<IdentEntry>, initial_text: &'i str) -> Self {
         Self {
             range: 0..idents.len(),
             all_idents: Rc::new(idents),
             initial_text,
             i: 0,
+            idents: Rc::new(OnceCell::new()),
         }
     }
 }
@@ -121,7 +132,7 @@ impl<'i, I: IdentTrait + 'i> Iterator for Pairs2<'i, I> {
         let end = unsafe {
             // This is safe if the data is valid.
             // The data is valid because it originally comes from `Pairs2::from_idents`, which is only called with valid data.
-            self.all_idents.get_unchecked(start).1
+            self.all_idents.get_unchecked(start).end_idx()
         };
         self.i = end - self.range.start;
 
@@ -129,6 +140,7 @@ impl<'i, I: IdentTrait + 'i> Iterator for Pairs2<'i, I> {
             all_idents: Rc::clone(&self.all_idents),
             original_input: self.initial_text,
             range: start..end,
+            idents: Rc::clone(&self.idents),
         })
     }
 }
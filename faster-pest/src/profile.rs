@@ -0,0 +1,22 @@
+use std::cell::Cell;
+
+thread_local! {
+    /// Whether choices of parsers with `profile_choices` count the alternatives that match.
+    static COUNTING: Cell<bool> = const { Cell::new(true) };
+}
+
+/// Tells whether choices count the alternatives that match, which they don't while an input is parsed again.
+/// This is used by the generated parser.
+#[inline]
+pub fn counting_choices() -> bool {
+    COUNTING.with(|counting| counting.get())
+}
+
+/// Runs a parse of an input that was already parsed, without counting the alternatives of choices a second time.
+/// This is used by the generated parser.
+pub fn parsing_again<T>(parse: impl FnOnce() -> T) -> T {
+    let counting = COUNTING.with(|counting| counting.replace(false));
+    let result = parse();
+    COUNTING.with(|cell| cell.set(counting));
+    result
+}
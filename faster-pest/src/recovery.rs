@@ -0,0 +1,81 @@
+use std::cell::{Cell, RefCell};
+use crate::*;
+
+/// Failure of a rule that recovered from it, with the `ERROR` ident that replaced the rule.
+pub struct Recovered {
+    node_idx: usize,
+    /// Length of the remaining input where the node starts, sentinel included.
+    remaining_bytes: usize,
+    error: Error,
+}
+
+thread_local! {
+    /// Failures of the rules that recovered during [`recovering`].
+    /// It is `None` outside of it, where rules don't recover.
+    static RECOVERED: RefCell<Option<Vec<Recovered>>> = const { RefCell::new(None) };
+    /// Whether rules don't recover for now, because an alternative of a choice could still match.
+    static SUSPENDED: Cell<bool> = const { Cell::new(false) };
+}
+
+pub(crate) fn is_recovering() -> bool {
+    !SUSPENDED.with(|suspended| suspended.get()) && RECOVERED.with(|recovered| recovered.borrow().is_some())
+}
+
+/// Runs an alternative of a choice, without letting rules recover if it `can_recover`.
+/// Choices only let them recover once none of their alternatives matched.
+/// This is used by the generated parser.
+#[inline(always)]
+pub fn suspending_recovery<E: ErrorSink, T>(can_recover: bool, parse: impl FnOnce() -> T) -> T {
+    if !can_recover || !E::recovers() {
+        return parse();
+    }
+    let suspended = SUSPENDED.with(|suspended| suspended.replace(true));
+    let result = parse();
+    SUSPENDED.with(|cell| cell.set(suspended));
+    result
+}
+
+pub(crate) fn record_recovered(error: Error, input: &[u8], node_idx: usize) {
+    RECOVERED.with(|recovered| {
+        if let Some(recovered) = recovered.borrow_mut().as_mut() {
+            recovered.push(Recovered { node_idx, remaining_bytes: input.len(), error });
+        }
+    });
+}
+
+/// Runs a parse during which rules declared with `recover` skip what they fail on, and returns their failures.
+/// This is used by the generated parser.
+pub fn recovering<T>(parse: impl FnOnce() -> Result<T, Error>) -> Result<(T, Vec<Recovered>), Error> {
+    RECOVERED.with(|recovered| recovered.replace(Some(Vec::new())));
+    let result = parse();
+    let recovered = RECOVERED.with(|recovered| recovered.take()).unwrap_or_default();
+    result.map(|value| (value, recovered))
+}
+
+/// Counts the bytes a rule that failed at the start of `input` skips, up to `terminator` or the end of the input.
+/// Returns `None` when there is nothing to skip, so that recovering always makes progress.
+/// This is used by the generated parser.
+#[cold]
+pub fn recovery_skip(input: &[u8], terminator: &[u8], padding: usize) -> Option<usize> {
+    match scan_until(&input[..input.len() - padding], terminator) {
+        0 => None,
+        skipped => Some(skipped),
+    }
+}
+
+/// Keeps the errors of the recoveries whose `ERROR` ident made it to the parsed idents.
+/// Recoveries can happen in alternatives that are then discarded, and be repeated when alternatives are retried.
+/// This is used by the generated parser.
+pub fn recovered_errors(idents: &[IdentEntry], error_variant: u16, recovered: Vec<Recovered>, padding: usize) -> Vec<Error> {
+    let mut errors: Vec<(usize, Error)> = Vec::new();
+    for Recovered { node_idx, remaining_bytes, error } in recovered {
+        let Some(node) = idents.get(node_idx) else { continue };
+        if node.variant() != error_variant || node.start_from_end() + padding != remaining_bytes {
+            continue;
+        }
+        errors.retain(|(idx, _)| *idx != node_idx);
+        errors.push((node_idx, error.without_padding(padding)));
+    }
+    errors.sort_by_key(|(idx, _)| *idx);
+    errors.into_iter().map(|(_, error)| error).collect()
+}
@@ -0,0 +1,6 @@
+/// Counts the bytes before the first occurrence of `literal`, or returns the length of the input if there is none.
+/// This is used by the generated parser for repetitions like `(!"*/" ~ ANY)*`.
+#[inline]
+pub fn scan_until(input: &[u8], literal: &[u8]) -> usize {
+    memchr::memmem::find(input, literal).unwrap_or(input.len())
+}
@@ -0,0 +1,28 @@
+/// Byte that parsers generated with `#[faster_pest(sentinel)]` expect right after their input.
+/// Character checks stop on it instead of checking bounds, so it is never part of any match.
+/// NUL bytes of the input itself are told apart with [`is_input_end`], only once a check has stopped on one.
+pub const SENTINEL: u8 = 0;
+
+/// Tells whether `c`, a byte of the padded input `input`, is the sentinel after it.
+/// This is used by the generated parser.
+#[inline(always)]
+pub fn is_input_end(input: &[u8], c: &u8) -> bool {
+    std::ptr::eq(c, input.as_ptr().wrapping_add(input.len() - 1))
+}
+
+/// Copies the input with a sentinel byte at the end.
+pub fn pad_input(input: &str) -> Vec<u8> {
+    let mut padded = Vec::with_capacity(input.len() + 1);
+    padded.extend_from_slice(input.as_bytes());
+    padded.push(SENTINEL);
+    padded
+}
+
+/// Returns the input of a padded string, without its trailing sentinel byte.
+/// Panics if the string doesn't end with a sentinel byte.
+pub fn strip_sentinel(padded: &str) -> &str {
+    match padded.as_bytes().last() {
+        Some(&SENTINEL) => unsafe { padded.get_unchecked(..padded.len() - 1) },
+        _ => panic!("padded input must end with a NUL byte"),
+    }
+}
@@ -0,0 +1,14 @@
+use faster_pest::*;
+
+#[derive(Parser)]
+#[grammar = "faster-pest/tests/grammars/choices.pest"]
+#[faster_pest(profile_choices)]
+pub struct ProfiledParser;
+
+#[test]
+fn invalid_inputs_are_counted_once() {
+    ProfiledParser::parse_file("abcdcdx").unwrap_err();
+    let profile = ProfiledParser::choice_profile();
+    assert!(profile.contains("1\t\"ab\"\n"), "{profile}");
+    assert!(profile.contains("2\t\"cd\"\n"), "{profile}");
+}
@@ -0,0 +1,17 @@
+use faster_pest::*;
+
+#[derive(Parser)]
+#[grammar = "faster-pest/tests/grammars/codepoints.pest"]
+struct CodepointParser;
+
+#[test]
+fn non_ascii_ranges_match_whole_codepoints() {
+    assert!(CodepointParser::parse_latin("àéÿ").is_ok());
+    assert!(CodepointParser::parse_latin("a").is_err());
+    assert!(CodepointParser::parse_latin("Ā").is_err());
+    assert!(CodepointParser::parse_mixed("a-é-\u{80}").is_ok());
+    assert!(CodepointParser::parse_mixed("ê").is_err());
+    assert!(CodepointParser::parse_wide("\u{7F0}\u{FFFF}\u{10000}\u{10400}").is_ok());
+    assert!(CodepointParser::parse_wide("\u{7EF}").is_err());
+    assert!(CodepointParser::parse_wide("\u{10401}").is_err());
+}
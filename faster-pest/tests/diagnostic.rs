@@ -0,0 +1,21 @@
+#![cfg(feature = "miette")]
+
+use faster_pest::*;
+use miette::Diagnostic;
+
+#[derive(Parser)]
+#[grammar = "faster-pest/tests/grammars/bytes.pest"]
+struct BytesParser;
+
+#[test]
+fn diagnostics_label_whole_characters_and_list_the_trace() {
+    let input = "é";
+    let error = BytesParser::parse_file(input).unwrap_err();
+    let trace_len = error.trace().len();
+    let error = error.with_source_code("input", input);
+    let labels = error.labels().unwrap().collect::<Vec<_>>();
+    assert_eq!(labels[0].offset(), 0);
+    assert_eq!(labels[0].len(), 2);
+    assert_eq!(labels[0].label(), Some("unexpected 'é'"));
+    assert_eq!(error.related().unwrap().count(), trace_len);
+}
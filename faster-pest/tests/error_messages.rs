@@ -0,0 +1,12 @@
+use faster_pest::*;
+
+#[test]
+fn kinds_display_in_the_same_style() {
+    assert_eq!(ErrorKind::ExpectedValue("{").to_string(), "expected `{`");
+    assert_eq!(ErrorKind::Expected("ASCII digit").to_string(), "expected ASCII digit");
+    assert_eq!(ErrorKind::ExpectedRule("string").to_string(), "expected string");
+    assert_eq!(ErrorKind::NegPredFailed("keyword").to_string(), "expected anything but keyword");
+    let one_of = ErrorKind::ExpectedOneOf(vec![ErrorKind::ExpectedValue("{"), ErrorKind::ExpectedRule("string")]);
+    assert_eq!(one_of.to_string(), "expected one of `{`, string");
+    assert_eq!(ErrorKind::ExpectedOneOf(Vec::new()).to_string(), "unexpected input");
+}
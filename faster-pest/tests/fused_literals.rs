@@ -0,0 +1,40 @@
+mod detailed {
+    use faster_pest::*;
+
+    #[derive(Parser)]
+    #[grammar = "faster-pest/tests/grammars/fused.pest"]
+    pub struct FusedParser;
+}
+
+mod offset {
+    use faster_pest::*;
+
+    #[derive(Parser)]
+    #[grammar = "faster-pest/tests/grammars/fused.pest"]
+    #[faster_pest(errors = "offset")]
+    pub struct OffsetParser;
+}
+
+use faster_pest::ErrorKind;
+
+#[test]
+fn fused_literals_fail_where_their_literal_does() {
+    use detailed::FusedParser;
+    use offset::OffsetParser;
+
+    let cases = [
+        ("ax", FusedParser::parse_chars("ax"), OffsetParser::parse_chars("ax"), 1, ErrorKind::ExpectedValue("b")),
+        ("a", FusedParser::parse_chars("a"), OffsetParser::parse_chars("a"), 1, ErrorKind::ExpectedValue("b")),
+        ("ax", FusedParser::parse_literals("ax"), OffsetParser::parse_literals("ax"), 0, ErrorKind::ExpectedRule("literals")),
+        ("abcx", FusedParser::parse_literals("abcx"), OffsetParser::parse_literals("abcx"), 2, ErrorKind::ExpectedValue("cd")),
+        ("abcdx", FusedParser::parse_literals("abcdx"), OffsetParser::parse_literals("abcdx"), 4, ErrorKind::ExpectedValue("e")),
+    ];
+    for (input, detailed, offset, expected_offset, expected) in cases {
+        let detailed = detailed.err().unwrap_or_else(|| panic!("{input:?} parsed"));
+        assert_eq!(detailed.offset(input), expected_offset, "{input:?}");
+        assert_eq!(detailed.kind(), &expected, "{input:?}");
+        let offset = offset.err().unwrap_or_else(|| panic!("{input:?} parsed"));
+        assert_eq!(offset.offset(input), expected_offset, "{input:?}");
+        assert_eq!(offset.kind(), &expected, "{input:?}");
+    }
+}
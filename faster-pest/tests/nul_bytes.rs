@@ -0,0 +1,33 @@
+mod sentinel {
+    use faster_pest::*;
+
+    #[derive(Parser)]
+    #[grammar = "faster-pest/tests/grammars/any.pest"]
+    #[faster_pest(sentinel)]
+    pub struct SentinelParser;
+}
+
+mod bounds {
+    use faster_pest::*;
+
+    #[derive(Parser)]
+    #[grammar = "faster-pest/tests/grammars/any.pest"]
+    pub struct BoundsParser;
+}
+
+#[test]
+fn sentinel_mode_accepts_the_same_nul_bytes() {
+    use bounds::BoundsParser;
+    use sentinel::SentinelParser;
+
+    for input in ["a\0b", "\0", "\0\0", "a\0", "\0\0\0", "\0x", ""] {
+        assert_eq!(SentinelParser::parse_file(input).is_ok(), BoundsParser::parse_file(input).is_ok(), "file on {input:?}");
+        assert_eq!(SentinelParser::parse_pair(input).is_ok(), BoundsParser::parse_pair(input).is_ok(), "pair on {input:?}");
+        assert_eq!(SentinelParser::parse_triple(input).is_ok(), BoundsParser::parse_triple(input).is_ok(), "triple on {input:?}");
+        assert_eq!(SentinelParser::parse_not_x(input).is_ok(), BoundsParser::parse_not_x(input).is_ok(), "not_x on {input:?}");
+    }
+    assert!(SentinelParser::parse_file("a\0b").is_ok());
+    assert!(SentinelParser::parse_pair("a\0").is_ok());
+    assert!(SentinelParser::parse_triple("\0\0\0").is_ok());
+    assert!(SentinelParser::parse_not_x("\0\0").is_ok());
+}
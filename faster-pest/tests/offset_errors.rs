@@ -0,0 +1,26 @@
+mod offset {
+    use faster_pest::*;
+
+    #[derive(Parser)]
+    #[grammar = "faster-pest/tests/grammars/optional.pest"]
+    #[faster_pest(errors = "offset")]
+    pub struct OffsetParser;
+}
+
+mod detailed {
+    use faster_pest::*;
+
+    #[derive(Parser)]
+    #[grammar = "faster-pest/tests/grammars/optional.pest"]
+    pub struct DetailedParser;
+}
+
+#[test]
+fn failures_in_optionals_are_the_furthest() {
+    let input = "ab1X";
+    let offset_error = offset::OffsetParser::parse_file(input).unwrap_err();
+    let detailed_error = detailed::DetailedParser::parse_file(input).unwrap_err();
+    assert_eq!(offset_error.offset(input), 3);
+    assert_eq!(offset_error.offset(input), detailed_error.offset(input));
+    assert_eq!(offset_error.kind(), detailed_error.kind());
+}
@@ -0,0 +1,42 @@
+mod detailed {
+    use faster_pest::*;
+
+    #[derive(Parser)]
+    #[grammar = "faster-pest/tests/grammars/recovery.pest"]
+    #[faster_pest(recover(line = "\n"))]
+    pub struct DetailedParser;
+}
+
+mod offset {
+    use faster_pest::*;
+
+    #[derive(Parser)]
+    #[grammar = "faster-pest/tests/grammars/recovery.pest"]
+    #[faster_pest(recover(line = "\n"), errors = "offset")]
+    pub struct OffsetParser;
+}
+
+const INPUT: &str = "#c\nk=1\nk=x\n#d";
+
+#[test]
+fn rules_only_recover_when_no_alternative_matches() {
+    let (file, errors) = detailed::DetailedParser::parse_file_recovering(INPUT).unwrap();
+    let children = file.root().children().map(|child| (child.as_rule(), child.as_str())).collect::<Vec<_>>();
+    assert_eq!(children, [
+        (detailed::Rule::comment, "#c"),
+        (detailed::Rule::line, "k=1"),
+        (detailed::Rule::ERROR, "k=x"),
+        (detailed::Rule::comment, "#d"),
+    ]);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].offset(INPUT), 9);
+}
+
+#[test]
+fn offset_errors_recover_the_same_way() {
+    let (file, errors) = offset::OffsetParser::parse_file_recovering(INPUT).unwrap();
+    let children = file.root().children().map(|child| child.as_rule()).collect::<Vec<_>>();
+    assert_eq!(children, [offset::Rule::comment, offset::Rule::line, offset::Rule::ERROR, offset::Rule::comment]);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].offset(INPUT), 9);
+}
@@ -0,0 +1,13 @@
+use faster_pest::*;
+
+#[derive(Parser)]
+#[grammar = "faster-pest/tests/grammars/repeats.pest"]
+struct RepeatsParser;
+
+#[test]
+fn bounded_repetitions_of_characters_describe_them() {
+    let input = "1a2";
+    let error = RepeatsParser::parse_file(input).unwrap_err();
+    assert_eq!(error.offset(input), 1);
+    assert_eq!(error.kind(), &ErrorKind::Expected("ASCII digit"));
+}
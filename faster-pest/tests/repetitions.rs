@@ -0,0 +1,32 @@
+use faster_pest::*;
+
+#[derive(Parser)]
+#[grammar = "faster-pest/tests/grammars/repetitions.pest"]
+struct RepetitionsParser;
+
+fn matched(idents: Result<IdentList<'_, Ident<'_>>, Error>) -> (String, usize) {
+    let idents = idents.unwrap();
+    let root = idents.root();
+    (root.as_str().to_string(), root.children_count())
+}
+
+#[test]
+fn whitespace_is_skipped_between_iterations() {
+    assert_eq!(matched(RepetitionsParser::parse_plus("x x x")), (String::from("x x x"), 3));
+    assert_eq!(matched(RepetitionsParser::parse_star("x x x")), (String::from("x x x"), 3));
+}
+
+#[test]
+fn whitespace_after_the_last_iteration_is_left() {
+    assert_eq!(matched(RepetitionsParser::parse_plus("x x ")), (String::from("x x"), 2));
+    assert_eq!(matched(RepetitionsParser::parse_star("x x ")), (String::from("x x"), 2));
+    assert_eq!(matched(RepetitionsParser::parse_star(" x")), (String::new(), 0));
+}
+
+#[test]
+fn counted_repetitions_skip_whitespace_between_iterations() {
+    assert_eq!(matched(RepetitionsParser::parse_range("x x x x")), (String::from("x x x"), 3));
+    assert_eq!(matched(RepetitionsParser::parse_range("x x ")), (String::from("x x"), 2));
+    assert_eq!(matched(RepetitionsParser::parse_at_least("x x x ")), (String::from("x x x"), 3));
+    assert!(RepetitionsParser::parse_range("x ").is_err());
+}
@@ -0,0 +1,19 @@
+use faster_pest::*;
+
+#[derive(Parser)]
+#[grammar = "faster-pest/tests/grammars/nul.pest"]
+#[faster_pest(sentinel)]
+struct SentinelParser;
+
+fn children(input: &str) -> Vec<Rule> {
+    let file = SentinelParser::parse_file(input).unwrap();
+    file.root().children().map(|child| child.as_rule()).collect()
+}
+
+#[test]
+fn literals_do_not_match_the_sentinel() {
+    assert_eq!(children("a"), []);
+    assert_eq!(children("b"), []);
+    assert_eq!(children("a\0"), [Rule::literal]);
+    assert_eq!(children("B\0"), [Rule::insensitive]);
+}
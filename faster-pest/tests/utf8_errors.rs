@@ -0,0 +1,18 @@
+use faster_pest::*;
+
+#[derive(Parser)]
+#[grammar = "faster-pest/tests/grammars/bytes.pest"]
+struct BytesParser;
+
+#[test]
+fn errors_inside_characters_point_to_their_start() {
+    let input = "é";
+    let error = BytesParser::parse_file(input).unwrap_err();
+    assert_eq!(error.offset(input), 0);
+    assert_eq!(error.span(input), 0..2);
+    assert_eq!(error.line_col(input).col_utf8, 1);
+    let mut rendered = String::new();
+    error.render(input, &mut rendered, RenderOptions::default()).unwrap();
+    let pest_error = error.into_pest::<Rule>(input);
+    assert_eq!(pest_error.line_col, pest::error::LineColLocation::Span((1, 1), (1, 2)));
+}
@@ -0,0 +1,12 @@
+use faster_pest::*;
+
+#[derive(Parser)]
+#[grammar = "faster-pest/tests/grammars/whitespace.pest"]
+struct WhitespaceParser;
+
+#[test]
+fn non_silent_comments_are_in_the_tree() {
+    let words = WhitespaceParser::parse_words("ab /* hi */ cd").unwrap();
+    let children = words.root().children().map(|child| child.as_rule()).collect::<Vec<_>>();
+    assert_eq!(children, [Rule::word, Rule::COMMENT, Rule::word]);
+}